@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+use std::hint::black_box;
+use std::sync::Arc;
+use std::time::Instant;
+
+use advent2019::intcode::compile::Compiled;
+use advent2019::intcode::{get_program, DecodeCache, Status, VM};
+
+fn start(program: &[i64], cache: Option<&Arc<DecodeCache>>) -> VM {
+    match cache {
+        Some(cache) => VM::with_decode_cache(Arc::clone(cache)),
+        None => VM::new(program.to_vec()),
+    }
+}
+
+fn diagnostic(program: &[i64], cache: Option<&Arc<DecodeCache>>) -> i64 {
+    let mut vm = start(program, cache);
+    let mut output = Vec::new();
+    vm.resume(&[5][..], &mut output).unwrap();
+    output[0]
+}
+
+fn diagnostic_compiled(compiled: &Compiled) -> i64 {
+    let mut machine = compiled.start();
+    let mut output = Vec::new();
+    compiled
+        .resume(&mut machine, &[5][..], &mut output)
+        .unwrap();
+    output[0]
+}
+
+fn permutations(values: &[i64]) -> Vec<Vec<i64>> {
+    if values.is_empty() {
+        return vec![Vec::new()];
+    }
+    let mut result = Vec::new();
+    for (i, value) in values.iter().enumerate() {
+        let mut rest = values.to_vec();
+        rest.remove(i);
+        for mut permutation in permutations(&rest) {
+            permutation.insert(0, *value);
+            result.push(permutation);
+        }
+    }
+    result
+}
+
+fn feedback_loop(program: &[i64], phase_settings: &[i64], cache: Option<&Arc<DecodeCache>>) -> i64 {
+    let mut queue = VecDeque::new();
+    for &phase in phase_settings {
+        let mut vm = start(program, cache);
+        vm.feed_inputs(&[phase]);
+        queue.push_back(vm);
+    }
+
+    let mut signal = VecDeque::from(vec![0]);
+    while let Some(mut vm) = queue.pop_front() {
+        let mut output = VecDeque::new();
+        if vm.resume(&mut signal, &mut output).unwrap().status == Status::SuspendInput {
+            queue.push_back(vm);
+        }
+        signal = output;
+    }
+    signal[0]
+}
+
+fn feedback_search(program: &[i64], cache: Option<&Arc<DecodeCache>>) -> i64 {
+    permutations(&[5, 6, 7, 8, 9])
+        .iter()
+        .map(|phase_settings| feedback_loop(program, phase_settings, cache))
+        .max()
+        .unwrap()
+}
+
+fn feedback_search_compiled(compiled: &Compiled) -> i64 {
+    let mut max = i64::MIN;
+    for phase_settings in permutations(&[5, 6, 7, 8, 9]) {
+        let mut queue = VecDeque::new();
+        for &phase in &phase_settings {
+            let mut machine = compiled.start();
+            compiled
+                .resume(&mut machine, &[phase][..], Vec::new())
+                .unwrap();
+            queue.push_back(machine);
+        }
+        let mut signal = VecDeque::from(vec![0]);
+        while let Some(mut machine) = queue.pop_front() {
+            let mut output = VecDeque::new();
+            let suspension = compiled.resume(&mut machine, &mut signal, &mut output);
+            if suspension.unwrap().status == Status::SuspendInput {
+                queue.push_back(machine);
+            }
+            signal = output;
+        }
+        max = max.max(signal[0]);
+    }
+    max
+}
+
+fn bench<F: FnMut() -> i64>(name: &str, iterations: u32, mut f: F) {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        black_box(f());
+    }
+    println!("{:<24} {:>12.2?}/iter", name, start.elapsed() / iterations);
+}
+
+fn main() {
+    let diagnostic_program = get_program(&include_bytes!("../input/05")[..]).unwrap();
+    let amplifier_program = get_program(&include_bytes!("../input/07")[..]).unwrap();
+
+    bench("05 uncached", 1000, || {
+        diagnostic(&diagnostic_program, None)
+    });
+    let cache = Arc::new(DecodeCache::new(&diagnostic_program));
+    bench("05 cached", 1000, || {
+        diagnostic(&diagnostic_program, Some(&cache))
+    });
+    let compiled = Compiled::new(&diagnostic_program);
+    bench("05 compiled", 1000, || diagnostic_compiled(&compiled));
+    bench("07-b uncached", 20, || {
+        feedback_search(&amplifier_program, None)
+    });
+    let cache = Arc::new(DecodeCache::new(&amplifier_program));
+    bench("07-b cached", 20, || {
+        feedback_search(&amplifier_program, Some(&cache))
+    });
+    let compiled = Compiled::new(&amplifier_program);
+    bench("07-b compiled", 20, || feedback_search_compiled(&compiled));
+}
@@ -2,55 +2,36 @@ use std::error::Error;
 use std::io;
 use std::io::prelude::*;
 
-type Result<T> = std::result::Result<T, Box<dyn Error + 'static>>;
+use advent2019::intcode::compile::Compiled;
+use advent2019::intcode::symbolic::evaluate;
+use advent2019::intcode::{get_program, Status};
 
-fn get_program<R: BufRead>(read: R) -> Result<Vec<u64>> {
-    let mut inputs = Vec::new();
-    for value in read.split(b',') {
-        let value = value?;
-        let value = std::str::from_utf8(&value)?;
-        let value = value.trim();
-        if value.is_empty() {
-            continue;
-        }
-        let number = value.parse::<u64>()?;
-        inputs.push(number)
-    }
-
-    Ok(inputs)
-}
+type Result<T> = std::result::Result<T, Box<dyn Error + 'static>>;
 
-fn restore(mut program: Vec<u64>, noun: u64, verb: u64) -> Vec<u64> {
-    program[1] = noun;
-    program[2] = verb;
-    program
+fn check(compiled: &Compiled, target: i64, noun: i64, verb: i64) -> bool {
+    let mut machine = compiled.start();
+    machine.set_memory(1, noun);
+    machine.set_memory(2, verb);
+    let halted = compiled
+        .resume(&mut machine, &[][..], Vec::new())
+        .is_ok_and(|suspension| suspension.status == Status::Halt);
+    halted && machine.memory()[0] == target
 }
 
-fn execute(mut program: Vec<u64>) -> Option<u64> {
-    let mut pc = 0;
-    while program[pc] != 99 {
-        if let &[op, a, b, c] = program.get(pc..pc + 4)? {
-            let a = *program.get(a as usize)?;
-            let b = *program.get(b as usize)?;
-            let c = program.get_mut(c as usize)?;
-            match op {
-                1 => *c = a + b,
-                2 => *c = a * b,
-                _ => return None,
-            }
-            pc += 4;
-        } else {
-            return None;
+// The symbolic answer is checked with one real run, since the VM can fail on overflow where
+// the expression doesn't.
+fn find(program: Vec<i64>, target: i64) -> Result<(i64, i64)> {
+    let mut compiled = Compiled::new(&program);
+    compiled.set_max_instructions(Some(MAX_INSTRUCTIONS));
+    let solved = evaluate(&program).and_then(|expr| expr.solve(target, 0..=99));
+    if let Some((noun, verb)) = solved {
+        if check(&compiled, target, noun, verb) {
+            return Ok((noun, verb));
         }
     }
-    Some(program[0])
-}
-
-fn find(program: Vec<u64>, target: u64) -> Result<(u64, u64)> {
     for noun in 0..=99 {
         for verb in 0..=99 {
-            let restored = restore(program.clone(), noun, verb);
-            if execute(restored) == Some(target) {
+            if check(&compiled, target, noun, verb) {
                 return Ok((noun, verb));
             }
         }
@@ -58,12 +39,13 @@ fn find(program: Vec<u64>, target: u64) -> Result<(u64, u64)> {
     Err("Not found")?
 }
 
-fn run<R: BufRead>(read: R, target: u64) -> Result<(u64, u64)> {
+fn run<R: BufRead>(read: R, target: i64) -> Result<(i64, i64)> {
     let program = get_program(read)?;
     find(program, target)
 }
 
-const TARGET: u64 = 19690720;
+const TARGET: i64 = 19690720;
+const MAX_INSTRUCTIONS: u64 = 10_000;
 
 fn main() -> Result<()> {
     let stdin = io::stdin();
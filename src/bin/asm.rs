@@ -0,0 +1,34 @@
+use std::error::Error;
+use std::io;
+use std::io::prelude::*;
+
+use advent2019::intcode::asm::assemble;
+
+type Result<T> = std::result::Result<T, Box<dyn Error + 'static>>;
+
+fn run<R: BufRead>(mut read: R) -> Result<String> {
+    let mut source = String::new();
+    read.read_to_string(&mut source)?;
+    let assembly = assemble(&source)?;
+    let words: Vec<String> = assembly.program.iter().map(i64::to_string).collect();
+    Ok(words.join(","))
+}
+
+fn main() -> Result<()> {
+    let stdin = io::stdin();
+    let program = run(stdin.lock())?;
+    println!("{}", program);
+    Ok(())
+}
+
+#[test]
+fn test() {
+    let source = "
+        in -> [9]
+        lt [9], #8 -> [9]
+        out [9]
+        hlt
+        .data 0
+    ";
+    assert_eq!(run(source.as_bytes()).unwrap(), "3,9,1007,9,8,9,4,9,99,0");
+}
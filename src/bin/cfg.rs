@@ -0,0 +1,51 @@
+use std::error::Error;
+use std::io;
+use std::io::prelude::*;
+
+use advent2019::intcode::cfg::{Cfg, ENTRIES_HELP};
+use advent2019::intcode::get_program;
+
+type Result<T> = std::result::Result<T, Box<dyn Error + 'static>>;
+
+const USAGE: &str = "usage: cfg [entries...] < program";
+
+fn run<R: BufRead>(read: R, entries: &[usize]) -> Result<Cfg> {
+    let program = get_program(read)?;
+    Ok(Cfg::build_from(&program, entries))
+}
+
+fn main() -> Result<()> {
+    let mut entries = vec![0];
+    for arg in std::env::args().skip(1) {
+        let entry = arg.parse();
+        entries.push(entry.map_err(|_| format!("{}\n\n{}", USAGE, ENTRIES_HELP))?);
+    }
+    let stdin = io::stdin();
+    let cfg = run(stdin.lock(), &entries)?;
+    for pc in cfg.indirect_jumps() {
+        eprintln!("indirect jump at {:04}", pc);
+    }
+    for pc in &cfg.modified {
+        eprintln!("instruction at {:04} is written to", pc);
+    }
+    print!("{}", cfg.to_dot());
+    Ok(())
+}
+
+#[test]
+fn test() {
+    let input = include_bytes!("../../input/05");
+    let cfg = run(&input[..], &[0]).unwrap();
+    assert!(cfg.indirect_jumps().is_empty());
+    assert!(cfg.written.contains(&6));
+    // The opcode at 0006 is only completed at runtime from the input value.
+    let dot = cfg.to_dot();
+    assert!(
+        dot.contains("b6 [label=\"0006: ?\\l\", style=\"dashed,filled\", fillcolor=lightpink];")
+    );
+    assert!(dot.contains("b0 -> b6;"));
+    assert!(!dot.contains("b7 "));
+    // Code past the unknown opcode can still be followed from an extra entry.
+    let dot = run(&input[..], &[0, 7]).unwrap().to_dot();
+    assert!(dot.contains("b7 [label=\"0007: ADD [238], [225] -> [104]\\l"));
+}
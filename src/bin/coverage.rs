@@ -0,0 +1,111 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+
+use advent2019::intcode::coverage::Coverage;
+use advent2019::intcode::{get_program, parse_runs, run_many, RUNS_HELP};
+
+type Result<T> = std::result::Result<T, Box<dyn Error + 'static>>;
+
+const USAGE: &str = "usage: coverage [--patch <patches>] <program> [inputs...]";
+
+const PATCH_HELP: &str = "\
+With --patch, the program is run once for every combination of the given
+comma-separated addr=value patches, where a value may also be a lo..hi range;
+`--patch 1=0..100,2=0..100` sweeps the noun and verb of day 2.";
+
+// Every combination of values, one patch list per patched copy of the program.
+fn parse_patches(arg: &str) -> Result<Vec<Vec<(usize, i64)>>> {
+    let mut patches = vec![Vec::new()];
+    for patch in arg.split(',') {
+        let (addr, value) = patch
+            .split_once('=')
+            .ok_or_else(|| format!("bad patch: {}", patch))?;
+        let addr = addr.trim().parse::<usize>()?;
+        let values = match value.split_once("..") {
+            Some((lo, hi)) => (lo.trim().parse::<i64>()?..hi.trim().parse::<i64>()?).collect(),
+            None => vec![value.trim().parse::<i64>()?],
+        };
+        patches = patches
+            .iter()
+            .flat_map(|patch| {
+                values.iter().map(move |&value| {
+                    let mut patch = patch.clone();
+                    patch.push((addr, value));
+                    patch
+                })
+            })
+            .collect();
+    }
+    Ok(patches)
+}
+
+fn coverage(program: &[i64], patches: &[Vec<(usize, i64)>], runs: &[Vec<i64>]) -> Result<Coverage> {
+    let mut total = Coverage::new();
+    for patch in patches {
+        let mut program = program.to_vec();
+        for &(addr, value) in patch {
+            if addr >= program.len() {
+                program.resize(addr + 1, 0);
+            }
+            program[addr] = value;
+        }
+        for vm in run_many(&program, runs, |vm| vm.set_coverage(true))? {
+            total.merge(vm.coverage().unwrap());
+        }
+    }
+    Ok(total)
+}
+
+fn main() -> Result<()> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let patches = if args.first().map(String::as_str) == Some("--patch") {
+        let patches = args.get(1).ok_or(USAGE)?;
+        let patches = parse_patches(patches)?;
+        args.drain(..2);
+        patches
+    } else {
+        vec![Vec::new()]
+    };
+    let path = args
+        .first()
+        .ok_or_else(|| format!("{}\n\n{}\n{}", USAGE, RUNS_HELP, PATCH_HELP))?;
+    let program = get_program(BufReader::new(File::open(path)?))?;
+    let runs = parse_runs(&args[1..])?;
+
+    print!("{}", coverage(&program, &patches, &runs)?.listing(&program));
+    Ok(())
+}
+
+#[test]
+fn test() {
+    use advent2019::intcode::coverage::Use;
+
+    let input = include_bytes!("../../input/05");
+    let program = get_program(&input[..]).unwrap();
+    let first = coverage(&program, &[vec![]], &[vec![1]]).unwrap();
+    let both = coverage(&program, &[vec![]], &[vec![1], vec![5]]).unwrap();
+    let (executed, _, untouched) = first.summary(program.len());
+    let (more, _, fewer) = both.summary(program.len());
+    assert!(more > executed && fewer < untouched);
+    // The jumps to 99999 that a failing self-test would take are never reached.
+    assert_eq!(both.classify(244), Use::Untouched);
+    assert!(both.listing(&program).contains(">  0000: IN -> [225]"));
+
+    let patches = parse_patches("1=0..3, 2=5").unwrap();
+    assert_eq!(
+        patches,
+        vec![
+            vec![(1, 0), (2, 5)],
+            vec![(1, 1), (2, 5)],
+            vec![(1, 2), (2, 5)]
+        ]
+    );
+    assert!(parse_patches("1").is_err());
+    let input = include_bytes!("../../input/02");
+    let program = get_program(&input[..]).unwrap();
+    let patches = parse_patches("1=0..10,2=0..10").unwrap();
+    let sweep = coverage(&program, &patches, &[vec![]]).unwrap();
+    // Between them the nouns and verbs read every one of the first ten words.
+    assert!((0..10).all(|addr| sweep.was_read(addr)));
+}
@@ -0,0 +1,381 @@
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+
+use advent2019::intcode::{get_program, Access, Instruction, Memory, Status, VmError, VM};
+
+type Result<T> = std::result::Result<T, Box<dyn Error + 'static>>;
+
+const HISTORY: usize = 1 << 20;
+
+const HELP: &str = "\
+step [n]            execute n instructions (default 1)
+continue            run until a breakpoint, input request or halt
+step-back [n]       undo n instructions (default 1)
+reverse-continue    run backwards until a breakpoint or watchpoint
+break <pc>          stop before the instruction at pc
+break-op <op>       stop before any instruction with this opcode or mnemonic
+break-output        toggle stopping after each output
+watch <addr> [n] [r|w|rw]
+                    stop after an access to n words starting at addr (default 1, rw)
+unwatch <addr> [n]  remove a watchpoint
+delete <pc|op>      remove a pc or opcode breakpoint
+breakpoints         list breakpoints
+inst                show the current instruction
+regs                show pc, relative base and instruction count
+x <addr> [n]        show n memory words starting at addr
+set <addr> <value>  write a memory word
+input <values...>   queue input values
+quit                leave the debugger";
+
+struct Debugger {
+    vm: VM,
+    opcode_breakpoints: BTreeSet<i64>,
+    break_on_output: bool,
+    halted: bool,
+}
+
+impl Debugger {
+    fn new(program: Vec<i64>) -> Debugger {
+        // Paged, so poking a far address doesn't allocate everything below it.
+        let mut vm = VM::with_memory(Memory::paged(&program));
+        vm.set_history(Some(HISTORY));
+        Debugger {
+            vm,
+            opcode_breakpoints: BTreeSet::new(),
+            break_on_output: false,
+            halted: false,
+        }
+    }
+
+    fn current(&self) -> std::result::Result<Instruction, String> {
+        let words = self.vm.memory().fetch(self.vm.pc());
+        Instruction::from_slice(&words).map_err(|kind| format!("{} ({})", kind, words[0]))
+    }
+
+    fn show_current<W: Write>(&self, out: &mut W) -> Result<()> {
+        match self.current() {
+            Ok(inst) => writeln!(out, "{:04}: {}", self.vm.pc(), inst)?,
+            Err(error) => writeln!(out, "{:04}: <{}>", self.vm.pc(), error)?,
+        }
+        Ok(())
+    }
+
+    fn stops_at_opcode(&self) -> bool {
+        self.current()
+            .map(|inst| self.opcode_breakpoints.contains(&inst.opcode()))
+            .unwrap_or(false)
+    }
+
+    fn run<W: Write>(&mut self, steps: Option<u64>, out: &mut W) -> Result<()> {
+        if self.halted {
+            writeln!(out, "program has halted")?;
+            return Ok(());
+        }
+        let mut outputs = Vec::new();
+        let stepping = steps.is_some() || !self.opcode_breakpoints.is_empty();
+        let mut executed = 0;
+        let result = loop {
+            let suspension = if stepping {
+                self.vm.step(&[][..], &mut outputs)
+            } else {
+                self.vm.resume(&[][..], &mut outputs)
+            };
+            let suspension = match suspension {
+                Ok(suspension) => suspension,
+                Err(error) => break Err(error),
+            };
+            executed += 1;
+            match suspension.status {
+                Status::Budget => {}
+                status => break Ok(status),
+            }
+            if steps == Some(executed) {
+                break Ok(Status::Budget);
+            }
+            if self.vm.breakpoints().contains(&self.vm.pc()) {
+                break Ok(Status::Breakpoint);
+            }
+            if self.stops_at_opcode() {
+                break Ok(Status::Breakpoint);
+            }
+        };
+
+        for value in outputs {
+            writeln!(out, "output: {}", value)?;
+        }
+        self.report(result, out)
+    }
+
+    fn report<W: Write>(
+        &mut self,
+        result: std::result::Result<Status, VmError>,
+        out: &mut W,
+    ) -> Result<()> {
+        match result {
+            Ok(Status::Halt) => {
+                self.halted = true;
+                writeln!(out, "halted after {} instructions", self.vm.count())?;
+                return Ok(());
+            }
+            Ok(Status::SuspendInput) => writeln!(out, "waiting for input")?,
+            Ok(Status::Breakpoint) => writeln!(out, "breakpoint")?,
+            Ok(Status::StartOfHistory) => writeln!(out, "start of history")?,
+            Ok(Status::Watchpoint(watch)) => {
+                let access = match watch.access {
+                    Access::Read => "read",
+                    _ => "write",
+                };
+                writeln!(
+                    out,
+                    "watchpoint: {:04}: {} {} [{}] {} -> {}",
+                    watch.pc, watch.instruction, access, watch.addr, watch.old, watch.new
+                )?
+            }
+            Ok(_) => {}
+            Err(error) => writeln!(out, "fault: {}", error)?,
+        }
+        self.show_current(out)
+    }
+
+    fn command<W: Write>(&mut self, line: &str, out: &mut W) -> Result<bool> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return Ok(true),
+        };
+        let number = |idx: usize| -> Result<i64> {
+            let arg = args.get(idx).ok_or("missing argument")?;
+            Ok(arg.parse::<i64>()?)
+        };
+        let addr = |idx: usize| -> Result<usize> {
+            let arg = args.get(idx).ok_or("missing argument")?;
+            Ok(arg.parse::<usize>()?)
+        };
+        let range = |start: usize, len: usize| -> Result<std::ops::Range<usize>> {
+            Ok(start..start.checked_add(len).ok_or("range out of bounds")?)
+        };
+
+        match command {
+            "s" | "step" => {
+                let steps = if args.is_empty() { 1 } else { addr(0)? };
+                if steps == 0 {
+                    Err("step count must be positive")?
+                }
+                self.run(Some(steps as u64), out)?;
+            }
+            "c" | "continue" => self.run(None, out)?,
+            "sb" | "step-back" => {
+                let steps = if args.is_empty() { 1 } else { addr(0)? };
+                for _ in 0..steps {
+                    if !self.vm.step_back() {
+                        writeln!(out, "start of history")?;
+                        break;
+                    }
+                }
+                self.halted = false;
+                self.show_current(out)?;
+            }
+            "rc" | "reverse-continue" => {
+                let suspension = self.vm.reverse_continue();
+                self.halted = false;
+                self.report(Ok(suspension.status), out)?;
+            }
+            "b" | "break" => self.vm.add_breakpoint(addr(0)?),
+            "bo" | "break-op" => {
+                let op = args.first().ok_or("missing argument")?;
+                let op = match op.parse::<i64>() {
+                    Ok(op) => op,
+                    Err(_) => opcode(op).ok_or("unknown mnemonic")?,
+                };
+                self.opcode_breakpoints.insert(op);
+            }
+            "bout" | "break-output" => {
+                self.break_on_output = !self.break_on_output;
+                self.vm.set_yield_on_output(self.break_on_output);
+                writeln!(out, "break on output: {}", self.break_on_output)?;
+            }
+            "w" | "watch" => {
+                let start = addr(0)?;
+                let (access, rest) = match args.get(1..).and_then(|rest| rest.split_last()) {
+                    Some((&"r", rest)) => (Access::Read, rest),
+                    Some((&"w", rest)) => (Access::Write, rest),
+                    Some((&"rw", rest)) => (Access::ReadWrite, rest),
+                    _ => (Access::ReadWrite, args.get(1..).unwrap_or(&[])),
+                };
+                let len = match rest {
+                    [] => 1,
+                    [len] => len.parse::<usize>()?,
+                    _ => Err("too many arguments")?,
+                };
+                self.vm.add_watchpoint(range(start, len)?, access);
+            }
+            "uw" | "unwatch" => {
+                let start = addr(0)?;
+                let len = if args.len() > 1 { addr(1)? } else { 1 };
+                if !self.vm.remove_watchpoint(range(start, len)?) {
+                    writeln!(out, "no watchpoint at {}", start)?;
+                }
+            }
+            "d" | "delete" => {
+                let arg = args.first().ok_or("missing argument")?;
+                match arg.parse::<usize>() {
+                    Ok(pc) => {
+                        self.vm.remove_breakpoint(pc);
+                    }
+                    Err(_) => {
+                        let op = opcode(arg).ok_or("unknown mnemonic")?;
+                        self.opcode_breakpoints.remove(&op);
+                    }
+                }
+            }
+            "bl" | "breakpoints" => {
+                for pc in self.vm.breakpoints() {
+                    writeln!(out, "pc {:04}", pc)?;
+                }
+                for op in &self.opcode_breakpoints {
+                    writeln!(out, "opcode {}", op)?;
+                }
+                for (range, access) in self.vm.watchpoints() {
+                    writeln!(
+                        out,
+                        "watch {:04}..{:04} {:?}",
+                        range.start, range.end, access
+                    )?;
+                }
+                if self.break_on_output {
+                    writeln!(out, "output")?;
+                }
+            }
+            "i" | "inst" => self.show_current(out)?,
+            "r" | "regs" => writeln!(
+                out,
+                "pc {} rb {} count {} input {:?}",
+                self.vm.pc(),
+                self.vm.relative_base(),
+                self.vm.count(),
+                self.vm.pending_inputs()
+            )?,
+            "x" => {
+                let start = addr(0)?;
+                let len = if args.len() > 1 { addr(1)? } else { 1 };
+                for addr in range(start, len)? {
+                    writeln!(out, "{:04}: {}", addr, self.vm.memory()[addr])?;
+                }
+            }
+            "set" => self.vm.set_memory(addr(0)?, number(1)?),
+            "in" | "input" => {
+                let mut values = Vec::new();
+                for idx in 0..args.len() {
+                    values.push(number(idx)?);
+                }
+                self.vm.feed_inputs(&values);
+            }
+            "h" | "help" => writeln!(out, "{}", HELP)?,
+            "q" | "quit" => return Ok(false),
+            _ => writeln!(out, "unknown command {:?}, try help", command)?,
+        }
+        Ok(true)
+    }
+}
+
+fn opcode(mnemonic: &str) -> Option<i64> {
+    let opcode = match mnemonic.to_ascii_lowercase().as_str() {
+        "add" => 1,
+        "mul" => 2,
+        "in" => 3,
+        "out" => 4,
+        "jt" => 5,
+        "jf" => 6,
+        "lt" => 7,
+        "eq" => 8,
+        "arb" => 9,
+        "hlt" => 99,
+        _ => return None,
+    };
+    Some(opcode)
+}
+
+fn main() -> Result<()> {
+    let path = std::env::args().nth(1).ok_or("usage: debugger <program>")?;
+    let program = get_program(BufReader::new(File::open(path)?))?;
+    let mut debugger = Debugger::new(program);
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    debugger.show_current(&mut out)?;
+    write!(out, "> ")?;
+    out.flush()?;
+    for line in stdin.lock().lines() {
+        match debugger.command(&line?, &mut out) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(error) => writeln!(out, "error: {}", error)?,
+        }
+        write!(out, "> ")?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test() {
+    let input = include_bytes!("../../input/05");
+    let mut debugger = Debugger::new(get_program(&input[..]).unwrap());
+    let mut out = Vec::new();
+    for line in &[
+        "step",
+        "input 5",
+        "watch 225 w",
+        "continue",
+        "unwatch 225",
+        "break-op out",
+        "continue",
+        "x 225 2",
+        "set 226 8",
+        "x 226",
+        "set 100000000000 1",
+        "x 100000000000",
+        "delete out",
+        "break-output",
+        "continue",
+        "watch 223 w",
+        "reverse-continue",
+        "step-back 2",
+    ] {
+        assert!(debugger.command(line, &mut out).unwrap());
+    }
+    for line in &[
+        "x 0 -1",
+        "watch 1 18446744073709551615",
+        "step 0",
+        "watch 225 abc",
+    ] {
+        assert!(debugger.command(line, &mut out).is_err());
+    }
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "\
+waiting for input
+0000: IN -> [225]
+watchpoint: 0000: IN -> [225] write [225] 0 -> 5
+0002: ADD [225], [6] -> [6]
+breakpoint
+0674: OUT [223]
+0225: 20
+0226: 677
+0226: 8
+100000000000: 1
+break on output: true
+output: 9265694
+0676: HLT
+watchpoint: 0663: MUL [223], #2 -> [223] write [223] 4632847 -> 9265694
+0663: MUL [223], #2 -> [223]
+0655: ADD [223], #1 -> [223]
+"
+    );
+}
@@ -0,0 +1,46 @@
+use std::error::Error;
+use std::io;
+use std::io::prelude::*;
+
+use advent2019::intcode::cfg::ENTRIES_HELP;
+use advent2019::intcode::decompile::decompile_from;
+use advent2019::intcode::get_program;
+
+type Result<T> = std::result::Result<T, Box<dyn Error + 'static>>;
+
+const USAGE: &str = "usage: decompile [entries...] < program";
+
+fn run<R: BufRead>(read: R, entries: &[usize]) -> Result<String> {
+    let program = get_program(read)?;
+    Ok(decompile_from(&program, entries))
+}
+
+fn main() -> Result<()> {
+    let mut entries = vec![0];
+    for arg in std::env::args().skip(1) {
+        let entry = arg.parse();
+        entries.push(entry.map_err(|_| format!("{}\n\n{}", USAGE, ENTRIES_HELP))?);
+    }
+    let stdin = io::stdin();
+    print!("{}", run(stdin.lock(), &entries)?);
+    Ok(())
+}
+
+#[test]
+fn test() {
+    // input/05 completes the opcode at 0006 only at runtime.
+    let input = include_bytes!("../../input/05");
+    let code = run(&input[..], &[0]).unwrap();
+    assert_eq!(
+        code,
+        "\
+var v6 = 1100;
+var v225 = 0;
+
+v225 = input();
+v6 = v225 + v6;
+goto L0006;
+L0006: // not decodable
+"
+    );
+}
@@ -0,0 +1,35 @@
+use std::error::Error;
+use std::io;
+use std::io::prelude::*;
+
+use advent2019::intcode::disasm::listing;
+use advent2019::intcode::get_program;
+
+type Result<T> = std::result::Result<T, Box<dyn Error + 'static>>;
+
+fn run<R: BufRead>(read: R) -> Result<String> {
+    let program = get_program(read)?;
+    Ok(listing(&program))
+}
+
+fn main() -> Result<()> {
+    let stdin = io::stdin();
+    let listing = run(stdin.lock())?;
+    print!("{}", listing);
+    Ok(())
+}
+
+#[test]
+fn test() {
+    let input = include_bytes!("../../input/05");
+    let listing = run(&input[..]).unwrap();
+    assert_eq!(
+        listing.lines().take(4).collect::<Vec<_>>(),
+        vec![
+            "0000: IN -> [225]",
+            "0002: ADD [225], [6] -> [6]",
+            "0006: .data 1100",
+            "0007: ADD [238], [225] -> [104]",
+        ]
+    );
+}
@@ -0,0 +1,90 @@
+use std::error::Error;
+use std::io;
+use std::io::prelude::*;
+
+use advent2019::intcode::asm::assemble;
+use advent2019::intcode::get_program;
+use advent2019::intcode::image::{Image, MAGIC};
+
+type Result<T> = std::result::Result<T, Box<dyn Error + 'static>>;
+
+const USAGE: &str = "usage: image [--asm] [--entry <addr|label>] < input > output
+
+Converts a comma-separated program into a binary image, or an image back
+into comma-separated text. With --asm the input is assembly source, and its
+labels go into the symbol table.";
+
+fn pack(input: &[u8], asm: bool, entry: Option<&str>) -> Result<Vec<u8>> {
+    let mut image = if asm {
+        Image::from_assembly(assemble(std::str::from_utf8(input)?)?)
+    } else {
+        Image::new(get_program(input)?)
+    };
+    if let Some(entry) = entry {
+        image.entry = match image.symbols.get(entry) {
+            Some(&addr) => addr,
+            None => entry.parse().map_err(|_| USAGE)?,
+        };
+    }
+    let mut output = Vec::new();
+    image.save(&mut output)?;
+    Ok(output)
+}
+
+fn unpack(input: &[u8]) -> Result<(Image, Vec<u8>)> {
+    let image = Image::load(input)?;
+    let words: Vec<String> = image.program.iter().map(i64::to_string).collect();
+    let output = format!("{}\n", words.join(","));
+    Ok((image, output.into_bytes()))
+}
+
+fn main() -> Result<()> {
+    let mut asm = false;
+    let mut entry = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--asm" => asm = true,
+            "--entry" => entry = Some(args.next().ok_or(USAGE)?),
+            _ => Err(USAGE)?,
+        }
+    }
+    let mut input = Vec::new();
+    io::stdin().read_to_end(&mut input)?;
+
+    let output = if input.starts_with(MAGIC) {
+        let (image, output) = unpack(&input)?;
+        eprintln!("entry {}", image.entry);
+        for (name, addr) in &image.symbols {
+            eprintln!("{} {}", name, addr);
+        }
+        output
+    } else {
+        pack(&input, asm, entry.as_deref())?
+    };
+    io::stdout().write_all(&output)?;
+    Ok(())
+}
+
+#[test]
+fn test() {
+    let source = "
+                jt #1, #start
+        x:      .data 0
+        start:  in -> [x]
+                out [x]
+                hlt
+    ";
+    let packed = pack(source.as_bytes(), true, Some("start")).unwrap();
+    let (image, text) = unpack(&packed).unwrap();
+    assert_eq!((image.entry, image.symbols["x"]), (4, 3));
+    assert_eq!(text, b"1105,1,4,0,3,3,4,3,99\n");
+
+    let input = include_bytes!("../../input/07");
+    let packed = pack(&input[..], false, None).unwrap();
+    let (_, text) = unpack(&packed).unwrap();
+    assert_eq!(
+        get_program(&text[..]).unwrap(),
+        get_program(&input[..]).unwrap()
+    );
+}
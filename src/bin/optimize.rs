@@ -0,0 +1,49 @@
+use std::error::Error;
+use std::io;
+use std::io::prelude::*;
+
+use advent2019::intcode::cfg::ENTRIES_HELP;
+use advent2019::intcode::get_program;
+use advent2019::intcode::optimize::{optimize_from, Optimized};
+
+type Result<T> = std::result::Result<T, Box<dyn Error + 'static>>;
+
+const USAGE: &str = "usage: optimize [entries...] < program";
+
+fn run<R: BufRead>(read: R, entries: &[usize]) -> Result<Optimized> {
+    let program = get_program(read)?;
+    Ok(optimize_from(&program, entries))
+}
+
+fn main() -> Result<()> {
+    let mut entries = vec![0];
+    for arg in std::env::args().skip(1) {
+        let entry = arg.parse();
+        entries.push(entry.map_err(|_| format!("{}\n\n{}", USAGE, ENTRIES_HELP))?);
+    }
+    let stdin = io::stdin();
+    let optimized = run(stdin.lock(), &entries)?;
+    eprintln!(
+        "{} folded, {} jumps simplified, {} words removed",
+        optimized.folded, optimized.jumps, optimized.removed
+    );
+    let words: Vec<String> = optimized.program.iter().map(i64::to_string).collect();
+    println!("{}", words.join(","));
+    Ok(())
+}
+
+#[test]
+fn test() {
+    use advent2019::intcode::VM;
+
+    // Outputs 1 if the input is 8, after a constant jump to the next instruction that
+    // compacting drops.
+    let input = "3,12,1008,12,8,12,1105,1,9,4,12,99,0";
+    let optimized = run(input.as_bytes(), &[0]).unwrap();
+    assert_eq!(optimized.program, vec![3, 9, 1008, 9, 8, 9, 4, 9, 99, 0]);
+    let mut output = Vec::new();
+    VM::new(optimized.program)
+        .resume(&[8][..], &mut output)
+        .unwrap();
+    assert_eq!(output, vec![1]);
+}
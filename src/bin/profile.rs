@@ -0,0 +1,95 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+
+use advent2019::intcode::profile::Profile;
+use advent2019::intcode::{get_program, parse_runs, run_many, Status, RUNS_HELP, VM};
+
+type Result<T> = std::result::Result<T, Box<dyn Error + 'static>>;
+
+const USAGE: &str = "usage: profile [--annotate] [--feedback] <program> [inputs...]";
+
+const FEEDBACK_HELP: &str = "\
+With --feedback, each inputs argument is instead a list of phase settings
+for a loop of amplifiers, as in day 7 part 2.";
+
+fn profile(program: &[i64], runs: &[Vec<i64>]) -> Result<Profile> {
+    let mut total = Profile::new();
+    for vm in run_many(program, runs, |vm| vm.set_profile(true))? {
+        total.merge(vm.profile().unwrap());
+    }
+    Ok(total)
+}
+
+// Each machine gets its phase first, then the signal the previous one put out, starting from 0.
+fn feedback(program: &[i64], runs: &[Vec<i64>]) -> Result<Profile> {
+    let mut total = Profile::new();
+    for phases in runs {
+        let mut machines: VecDeque<VM> = phases
+            .iter()
+            .map(|&phase| {
+                let mut vm = VM::new(program.to_vec());
+                vm.set_profile(true);
+                vm.feed_inputs(&[phase]);
+                vm
+            })
+            .collect();
+        let mut signal = VecDeque::from(vec![0]);
+        while let Some(mut vm) = machines.pop_front() {
+            let mut output = VecDeque::new();
+            let status = vm.resume(&mut signal, &mut output)?.status;
+            signal = output;
+            match status {
+                Status::SuspendInput => machines.push_back(vm),
+                _ => total.merge(vm.profile().unwrap()),
+            }
+        }
+    }
+    Ok(total)
+}
+
+fn main() -> Result<()> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let mut flag = |name: &str| {
+        let found = args.first().map(String::as_str) == Some(name);
+        if found {
+            args.remove(0);
+        }
+        found
+    };
+    let annotate = flag("--annotate");
+    let looped = flag("--feedback");
+    let path = args
+        .first()
+        .ok_or_else(|| format!("{}\n\n{}\n{}", USAGE, RUNS_HELP, FEEDBACK_HELP))?;
+    let program = get_program(BufReader::new(File::open(path)?))?;
+    let runs = parse_runs(&args[1..])?;
+
+    let profile = if looped {
+        feedback(&program, &runs)?
+    } else {
+        profile(&program, &runs)?
+    };
+    print!("{}", profile.report(&program));
+    if annotate {
+        print!("\n{}", profile.annotate(&program));
+    }
+    Ok(())
+}
+
+#[test]
+fn test() {
+    let input = include_bytes!("../../input/07");
+    let program = get_program(&input[..]).unwrap();
+    let runs: Vec<_> = (0..5).map(|phase| vec![phase, 0]).collect();
+    let profile = profile(&program, &runs).unwrap();
+    assert_eq!(profile.per_opcode[&3], 10);
+    assert!(!profile.per_opcode.contains_key(&99));
+    assert_eq!(profile.branches.values().map(|b| b.taken).sum::<u64>(), 5);
+
+    // Every amplifier reads its phase, then one signal for each one it puts out.
+    let profile = feedback(&program, &[vec![9, 8, 7, 6, 5]]).unwrap();
+    assert_eq!(profile.per_opcode[&3], profile.per_opcode[&4] + 5);
+    assert!(profile.per_opcode[&4] > 5);
+}
@@ -0,0 +1,56 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+
+use advent2019::intcode::selfmod::SelfModification;
+use advent2019::intcode::{get_program, parse_runs, run_many, RUNS_HELP};
+
+type Result<T> = std::result::Result<T, Box<dyn Error + 'static>>;
+
+const USAGE: &str = "usage: selfmod <program> [inputs...]";
+
+const SAFE_HELP: &str = "\
+A program without self-modifying writes is safe to disassemble, cache and
+compile as loaded.";
+
+fn detect(program: &[i64], runs: &[Vec<i64>]) -> Result<SelfModification> {
+    let mut total = SelfModification::new();
+    for vm in run_many(program, runs, |vm| vm.set_self_modification(true))? {
+        total.merge(vm.self_modification().unwrap());
+    }
+    Ok(total)
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let path = args
+        .first()
+        .ok_or_else(|| format!("{}\n\n{}\n{}", USAGE, RUNS_HELP, SAFE_HELP))?;
+    let program = get_program(BufReader::new(File::open(path)?))?;
+    let runs = parse_runs(&args[1..])?;
+
+    print!("{}", detect(&program, &runs)?.report());
+    Ok(())
+}
+
+#[test]
+fn test() {
+    use advent2019::intcode::selfmod::Order;
+
+    let input = include_bytes!("../../input/07");
+    let program = get_program(&input[..]).unwrap();
+    let found = detect(&program, &[vec![0, 0], vec![4, 0]]).unwrap();
+    // The phase setting picks the dispatch target of the jump at 0006.
+    assert!(found
+        .report()
+        .contains("0002: writes [8], param 2 of 0006, before it runs (2x)"));
+
+    // input/02 only stores over words that have already run and won't run again.
+    let input = include_bytes!("../../input/02");
+    let mut program = get_program(&input[..]).unwrap();
+    program[1] = 12;
+    program[2] = 2;
+    let found = detect(&program, &[vec![]]).unwrap();
+    let sites = found.sites();
+    assert!(!sites.is_empty() && sites.iter().all(|(site, _)| site.order == Order::After));
+}
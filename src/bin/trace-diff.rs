@@ -0,0 +1,86 @@
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+
+use advent2019::intcode::trace::{read_trace, Record};
+
+type Result<T> = std::result::Result<T, Box<dyn Error + 'static>>;
+
+const CONTEXT: usize = 3;
+
+fn diff<W: Write>(a: &[Record], b: &[Record], out: &mut W) -> Result<bool> {
+    let common = a.iter().zip(b).take_while(|(a, b)| a == b).count();
+    if common == a.len() && common == b.len() {
+        writeln!(out, "traces are identical ({} records)", common)?;
+        return Ok(false);
+    }
+
+    writeln!(out, "traces diverge after {} records", common)?;
+    for record in &a[common.saturating_sub(CONTEXT)..common] {
+        writeln!(out, "  {}", record)?;
+    }
+    match a.get(common) {
+        Some(record) => writeln!(out, "- {}", record)?,
+        None => writeln!(out, "- <end of trace>")?,
+    }
+    match b.get(common) {
+        Some(record) => writeln!(out, "+ {}", record)?,
+        None => writeln!(out, "+ <end of trace>")?,
+    }
+
+    // Different inputs usually only change values at first; the branch they lead to matters more.
+    let flow = a.iter().zip(b).take_while(|(a, b)| a.pc == b.pc).count();
+    if flow > common && flow < a.len().min(b.len()) {
+        writeln!(out, "control flow diverges after {} records", flow)?;
+        writeln!(out, "- {}", a[flow])?;
+        writeln!(out, "+ {}", b[flow])?;
+    }
+    Ok(true)
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() != 2 {
+        Err("usage: trace-diff <trace-a> <trace-b>")?
+    }
+    let a = read_trace(BufReader::new(File::open(&args[0])?))?;
+    let b = read_trace(BufReader::new(File::open(&args[1])?))?;
+    let stdout = io::stdout();
+    if diff(&a, &b, &mut stdout.lock())? {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[test]
+fn test() {
+    use advent2019::intcode::{get_program, VM};
+
+    let input = include_bytes!("../../input/07");
+    let program = get_program(&input[..]).unwrap();
+    let trace = |phase: i64| {
+        let mut trace = Vec::new();
+        let mut vm = VM::new(program.clone());
+        vm.resume_traced(&[phase, 0][..], Vec::new(), &mut trace)
+            .unwrap();
+        trace
+    };
+
+    let mut out = Vec::new();
+    assert!(!diff(&trace(3), &trace(3), &mut out).unwrap());
+    out.clear();
+    assert!(diff(&trace(3), &trace(4), &mut out).unwrap());
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "\
+traces diverge after 0 records
+- #0 0000: IN -> [8] [8] 0 -> 3 in 3
++ #0 0000: IN -> [8] [8] 0 -> 4 in 4
+control flow diverges after 3 records
+- #3 0064: IN -> [9] [9] 0 -> 0 in 0
++ #3 0085: IN -> [9] [9] 0 -> 0 in 0
+"
+    );
+}
@@ -0,0 +1,65 @@
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+
+use advent2019::intcode::get_program;
+use advent2019::intcode::trace::{Format, TraceWriter};
+use advent2019::intcode::{Status, VM};
+
+type Result<T> = std::result::Result<T, Box<dyn Error + 'static>>;
+
+const USAGE: &str = "usage: trace [--binary] <program> [inputs...]";
+
+fn run<W: Write>(program: Vec<i64>, inputs: &[i64], format: Format, write: W) -> Result<Status> {
+    let mut vm = VM::new(program);
+    vm.feed_inputs(inputs);
+    let mut writer = TraceWriter::new(write, format)?;
+    let suspension = vm.resume_traced(&[][..], Vec::new(), &mut writer)?;
+    writer.into_inner().flush()?;
+    Ok(suspension.status)
+}
+
+fn main() -> Result<()> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let format = if args.first().map(String::as_str) == Some("--binary") {
+        args.remove(0);
+        Format::Binary
+    } else {
+        Format::Json
+    };
+    let path = args.first().ok_or(USAGE)?;
+    let program = get_program(BufReader::new(File::open(path)?))?;
+    let mut inputs = Vec::new();
+    for arg in &args[1..] {
+        inputs.push(arg.parse()?);
+    }
+
+    let stdout = io::stdout();
+    let status = run(program, &inputs, format, io::BufWriter::new(stdout.lock()))?;
+    if status != Status::Halt {
+        eprintln!("stopped: {:?}", status);
+    }
+    Ok(())
+}
+
+#[test]
+fn test() {
+    let input = include_bytes!("../../input/05");
+    let program = get_program(&input[..]).unwrap();
+    let mut trace = Vec::new();
+    let status = run(program, &[1], Format::Json, &mut trace).unwrap();
+    assert_eq!(status, Status::Halt);
+    let trace = String::from_utf8(trace).unwrap();
+    assert_eq!(
+        trace.lines().next().unwrap(),
+        "{\"count\":0,\"pc\":0,\"words\":[3,225],\"instruction\":\"IN -> [225]\",\
+         \"operands\":[],\"write\":{\"addr\":225,\"old\":0,\"new\":1},\"input\":1}"
+    );
+    assert!(trace
+        .lines()
+        .last()
+        .unwrap()
+        .contains("\"instruction\":\"HLT\""));
+}
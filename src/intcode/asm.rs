@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+use super::instruction::{Instruction, Param};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assembly {
+    pub program: Vec<i64>,
+    pub labels: BTreeMap<String, usize>,
+}
+
+enum Statement<'a> {
+    Instruction(&'a str, Vec<&'a str>),
+    Data(Vec<&'a str>),
+}
+
+struct Parsed<'a> {
+    line: usize,
+    statement: Statement<'a>,
+}
+
+pub fn assemble(source: &str) -> Result<Assembly, AsmError> {
+    let mut labels = BTreeMap::new();
+    let mut statements = Vec::new();
+    let mut addr = 0;
+    for (idx, text) in source.lines().enumerate() {
+        let line = idx + 1;
+        let error = |message: String| AsmError { line, message };
+        let mut text = text.split(';').next().unwrap_or("").trim();
+        while let Some(colon) = text.find(':') {
+            let label = text[..colon].trim();
+            if label.chars().all(|c| c.is_ascii_digit()) && !label.is_empty() {
+                // Addresses from a disassembly listing must match where we are.
+                if label.parse::<usize>().ok() != Some(addr) {
+                    return Err(error(format!("address {} is at {}", label, addr)));
+                }
+            } else if is_symbol(label) {
+                if labels.insert(label.to_string(), addr).is_some() {
+                    return Err(error(format!("duplicate label {}", label)));
+                }
+            } else {
+                return Err(error(format!("invalid label {:?}", label)));
+            }
+            text = text[colon + 1..].trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let (head, rest) = text.split_at(text.find(char::is_whitespace).unwrap_or(text.len()));
+        let operands: Vec<&str> = rest
+            .split(',')
+            .flat_map(|operand| operand.split("->"))
+            .map(str::trim)
+            .filter(|operand| !operand.is_empty())
+            .collect();
+        let statement = if head.eq_ignore_ascii_case(".data") {
+            Statement::Data(operands)
+        } else {
+            Statement::Instruction(head, operands)
+        };
+        let size = match &statement {
+            Statement::Data(words) => words.len(),
+            Statement::Instruction(mnemonic, _) => {
+                size(mnemonic).ok_or_else(|| error(format!("unknown mnemonic {}", mnemonic)))?
+            }
+        };
+        statements.push(Parsed { line, statement });
+        addr += size;
+    }
+
+    let mut program = Vec::new();
+    for parsed in statements {
+        let line = parsed.line;
+        let error = |message: String| AsmError { line, message };
+        match parsed.statement {
+            Statement::Data(words) => {
+                for word in words {
+                    program.push(evaluate(word, &labels).map_err(error)?);
+                }
+            }
+            Statement::Instruction(mnemonic, operands) => {
+                let mut params = Vec::new();
+                for operand in operands {
+                    params.push(parse_param(operand, &labels).map_err(error)?);
+                }
+                let inst = build(mnemonic, &params).map_err(error)?;
+                program.extend(inst.encode());
+            }
+        }
+    }
+    Ok(Assembly { program, labels })
+}
+
+fn size(mnemonic: &str) -> Option<usize> {
+    let size = match mnemonic.to_ascii_lowercase().as_str() {
+        "hlt" => 1,
+        "in" | "out" | "arb" => 2,
+        "jt" | "jf" => 3,
+        "add" | "mul" | "lt" | "eq" => 4,
+        _ => return None,
+    };
+    Some(size)
+}
+
+fn build(mnemonic: &str, params: &[Param]) -> Result<Instruction, String> {
+    let mnemonic = mnemonic.to_ascii_lowercase();
+    let expected = size(&mnemonic).unwrap_or(1) - 1;
+    if params.len() != expected {
+        return Err(format!(
+            "{} takes {} operands, got {}",
+            mnemonic,
+            expected,
+            params.len()
+        ));
+    }
+    let inst = match mnemonic.as_str() {
+        "hlt" => Instruction::Halt,
+        "add" => Instruction::Add {
+            a: params[0],
+            b: params[1],
+            c: params[2],
+        },
+        "mul" => Instruction::Mul {
+            a: params[0],
+            b: params[1],
+            c: params[2],
+        },
+        "in" => Instruction::Input(params[0]),
+        "out" => Instruction::Output(params[0]),
+        "jt" => Instruction::JumpIfTrue {
+            cond: params[0],
+            next: params[1],
+        },
+        "jf" => Instruction::JumpIfFalse {
+            cond: params[0],
+            next: params[1],
+        },
+        "lt" => Instruction::LessThan {
+            a: params[0],
+            b: params[1],
+            c: params[2],
+        },
+        "eq" => Instruction::Eq {
+            a: params[0],
+            b: params[1],
+            c: params[2],
+        },
+        "arb" => Instruction::AdjustRelativeBase(params[0]),
+        _ => return Err(format!("unknown mnemonic {}", mnemonic)),
+    };
+    if let Some(Param::Immediate(_)) = inst.target() {
+        return Err(format!("{} cannot write to an immediate", mnemonic));
+    }
+    Ok(inst)
+}
+
+fn parse_param(operand: &str, labels: &BTreeMap<String, usize>) -> Result<Param, String> {
+    if let Some(value) = operand.strip_prefix('#') {
+        return Ok(Param::Immediate(evaluate(value, labels)?));
+    }
+    let inner = match operand.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+        Some(inner) => inner.trim(),
+        None => operand,
+    };
+    if let Some(offset) = inner.strip_prefix("rb") {
+        let offset = offset.trim();
+        let offset = if offset.is_empty() {
+            0
+        } else if let Some(offset) = offset.strip_prefix('+') {
+            evaluate(offset, labels)?
+        } else if offset.starts_with('-') {
+            evaluate(offset, labels)?
+        } else {
+            return Err(format!("invalid relative operand {}", operand));
+        };
+        return Ok(Param::Relative(offset));
+    }
+    let addr = evaluate(inner, labels)?;
+    let addr = usize::try_from(addr).map_err(|_| format!("negative address {}", addr))?;
+    Ok(Param::Position(addr))
+}
+
+fn evaluate(expr: &str, labels: &BTreeMap<String, usize>) -> Result<i64, String> {
+    let expr = expr.trim();
+    if let Ok(value) = expr.parse::<i64>() {
+        return Ok(value);
+    }
+    let split = expr.rfind(['+', '-']).filter(|idx| *idx > 0);
+    let (symbol, offset) = match split {
+        Some(idx) => {
+            let offset = expr[idx..].replace(' ', "");
+            let offset = offset.trim_start_matches('+');
+            let offset = offset
+                .parse::<i64>()
+                .map_err(|_| format!("invalid expression {}", expr))?;
+            (expr[..idx].trim(), offset)
+        }
+        None => (expr, 0),
+    };
+    match labels.get(symbol) {
+        Some(addr) => Ok(*addr as i64 + offset),
+        None if is_symbol(symbol) => Err(format!("undefined label {}", symbol)),
+        None => Err(format!("invalid expression {}", expr)),
+    }
+}
+
+fn is_symbol(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_') && name != "rb"
+}
+
+#[test]
+fn test() {
+    let source = "
+        ; outputs 1 if the input equals 8, 0 otherwise
+        start:  in -> [value]
+                eq [value], #8 -> [value]
+                out [value]
+                hlt
+        value:  .data 0
+    ";
+    let assembly = assemble(source).unwrap();
+    assert_eq!(assembly.program, vec![3, 9, 1008, 9, 8, 9, 4, 9, 99, 0]);
+    assert_eq!(assembly.labels["value"], 9);
+
+    let source = "loop: arb #2\n add [rb-2], #1 -> [rb+1]\n jt #1, #loop\n";
+    assert_eq!(
+        assemble(source).unwrap().program,
+        vec![109, 2, 21201, -2, 1, 1, 1105, 1, 0]
+    );
+
+    let error = assemble("add #1, #2 -> #3").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "line 1: add cannot write to an immediate"
+    );
+    let error = assemble("\njt #1, #nowhere").unwrap_err();
+    assert_eq!(error.to_string(), "line 2: undefined label nowhere");
+
+    for input in &[
+        &include_bytes!("../../input/05")[..],
+        include_bytes!("../../input/07"),
+    ] {
+        let program = super::get_program(*input).unwrap();
+        let listing = super::disasm::listing(&program);
+        assert_eq!(assemble(&listing).unwrap().program, program);
+    }
+}
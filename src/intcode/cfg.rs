@@ -0,0 +1,284 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use super::disasm::decode_at;
+use super::instruction::{Instruction, Param};
+
+pub const ENTRIES_HELP: &str = "\
+Code is followed from address 0 and any extra entry addresses, which is
+how the targets of jumps through memory get included.";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EdgeKind {
+    Fallthrough,
+    Taken,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Edge {
+    pub target: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub start: usize,
+    pub instructions: Vec<(usize, Instruction)>,
+    pub successors: Vec<Edge>,
+    pub indirect: bool,
+}
+
+impl Block {
+    pub fn end(&self) -> usize {
+        let (addr, inst) = self.instructions.last().unwrap();
+        addr + inst.pc_offset()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cfg {
+    pub blocks: BTreeMap<usize, Block>,
+    pub undecodable: BTreeSet<usize>,
+    pub written: BTreeSet<usize>,
+    pub modified: BTreeSet<usize>,
+}
+
+// The statically known successors of an instruction. A jump whose target comes from memory
+// can't be followed, so it reports `indirect` instead.
+fn successors(addr: usize, inst: &Instruction) -> (Vec<Edge>, bool) {
+    let fallthrough = Edge {
+        target: addr + inst.pc_offset(),
+        kind: EdgeKind::Fallthrough,
+    };
+    let (cond, next, jump_if) = match *inst {
+        Instruction::Halt => return (vec![], false),
+        Instruction::JumpIfTrue { cond, next } => (cond, next, true),
+        Instruction::JumpIfFalse { cond, next } => (cond, next, false),
+        _ => return (vec![fallthrough], false),
+    };
+    let (may_jump, may_fall) = match cond {
+        Param::Immediate(value) => ((value != 0) == jump_if, (value != 0) != jump_if),
+        _ => (true, true),
+    };
+
+    let mut edges = Vec::new();
+    let mut indirect = false;
+    if may_jump {
+        match next {
+            Param::Immediate(target) if target >= 0 => edges.push(Edge {
+                target: target as usize,
+                kind: EdgeKind::Taken,
+            }),
+            Param::Immediate(_) => {}
+            _ => indirect = true,
+        }
+    }
+    if may_fall {
+        edges.push(fallthrough);
+    }
+    (edges, indirect)
+}
+
+fn ends_block(inst: &Instruction) -> bool {
+    matches!(
+        inst,
+        Instruction::Halt | Instruction::JumpIfTrue { .. } | Instruction::JumpIfFalse { .. }
+    )
+}
+
+impl Cfg {
+    pub fn build(program: &[i64]) -> Cfg {
+        Cfg::build_from(program, &[0])
+    }
+
+    pub fn build_from(program: &[i64], entries: &[usize]) -> Cfg {
+        let mut instructions = BTreeMap::new();
+        let mut leaders: BTreeSet<usize> = entries.iter().copied().collect();
+        let mut undecodable = BTreeSet::new();
+        let mut pending: Vec<usize> = entries.to_vec();
+        while let Some(addr) = pending.pop() {
+            if instructions.contains_key(&addr) {
+                continue;
+            }
+            let inst = match decode_at(program, addr) {
+                Some(inst) => inst,
+                None => {
+                    undecodable.insert(addr);
+                    continue;
+                }
+            };
+            instructions.insert(addr, inst);
+            let (edges, _) = successors(addr, &inst);
+            for edge in edges {
+                if ends_block(&inst) {
+                    leaders.insert(edge.target);
+                }
+                pending.push(edge.target);
+            }
+        }
+
+        let mut blocks = BTreeMap::new();
+        let mut current: Option<Block> = None;
+        for (&addr, &inst) in &instructions {
+            let contiguous = current.as_ref().is_some_and(|block| block.end() == addr);
+            if !contiguous || leaders.contains(&addr) {
+                if let Some(block) = current.take() {
+                    blocks.insert(block.start, block);
+                }
+            }
+            let block = current.get_or_insert_with(|| Block {
+                start: addr,
+                instructions: Vec::new(),
+                successors: Vec::new(),
+                indirect: false,
+            });
+            block.instructions.push((addr, inst));
+            if ends_block(&inst) {
+                blocks.insert(block.start, current.take().unwrap());
+            }
+        }
+        if let Some(block) = current {
+            blocks.insert(block.start, block);
+        }
+
+        for block in blocks.values_mut() {
+            let (addr, inst) = *block.instructions.last().unwrap();
+            let (edges, indirect) = successors(addr, &inst);
+            block.successors = edges
+                .into_iter()
+                .filter(|edge| {
+                    instructions.contains_key(&edge.target) || undecodable.contains(&edge.target)
+                })
+                .collect();
+            block.indirect = indirect;
+        }
+
+        let written: BTreeSet<usize> = instructions
+            .values()
+            .filter_map(|inst| match inst.target() {
+                Some(Param::Position(addr)) => Some(addr),
+                _ => None,
+            })
+            .collect();
+        let modified = instructions
+            .iter()
+            .filter(|(&addr, inst)| {
+                written
+                    .range(addr..addr + inst.pc_offset())
+                    .next()
+                    .is_some()
+            })
+            .map(|(&addr, _)| addr)
+            .collect();
+
+        Cfg {
+            blocks,
+            undecodable,
+            written,
+            modified,
+        }
+    }
+
+    pub fn indirect_jumps(&self) -> Vec<usize> {
+        let blocks = self.blocks.values().filter(|block| block.indirect);
+        blocks
+            .map(|block| block.instructions.last().unwrap().0)
+            .collect()
+    }
+
+    // Blocks holding self-modified code are filled, and the modified instructions are starred.
+    // Words that don't decode yet get a dashed node, filled too if the program writes them.
+    // Indirect jumps get a dashed edge into a shared `indirect` node.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        dot.push_str("digraph cfg {\n");
+        dot.push_str("    node [shape=box, fontname=monospace];\n");
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            let mut modified = false;
+            for (addr, inst) in &block.instructions {
+                let star = if self.modified.contains(addr) {
+                    modified = true;
+                    "* "
+                } else {
+                    ""
+                };
+                write!(label, "{}{:04}: {}\\l", star, addr, inst).unwrap();
+            }
+            let style = if modified {
+                ", style=filled, fillcolor=lightpink"
+            } else {
+                ""
+            };
+            writeln!(dot, "    b{} [label=\"{}\"{}];", block.start, label, style).unwrap();
+        }
+        for addr in &self.undecodable {
+            let style = if self.written.contains(addr) {
+                "style=\"dashed,filled\", fillcolor=lightpink"
+            } else {
+                "style=dashed"
+            };
+            writeln!(
+                dot,
+                "    b{} [label=\"{:04}: ?\\l\", {}];",
+                addr, addr, style
+            )
+            .unwrap();
+        }
+        let indirect = self.blocks.values().any(|block| block.indirect);
+        if indirect {
+            dot.push_str("    indirect [shape=diamond, label=\"?\"];\n");
+        }
+        for block in self.blocks.values() {
+            for edge in &block.successors {
+                let label = match edge.kind {
+                    EdgeKind::Fallthrough => "",
+                    EdgeKind::Taken => " [label=\"taken\"]",
+                };
+                writeln!(dot, "    b{} -> b{}{};", block.start, edge.target, label).unwrap();
+            }
+            if block.indirect {
+                writeln!(dot, "    b{} -> indirect [style=dashed];", block.start).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[test]
+fn test() {
+    // Reads a number, then counts it down to zero; 0012 is unreachable.
+    let program = vec![3, 14, 1001, 14, -1, 14, 1005, 14, 2, 4, 14, 99, 1106, 0, 0];
+    let cfg = Cfg::build(&program);
+    assert_eq!(
+        cfg.blocks.keys().copied().collect::<Vec<_>>(),
+        vec![0, 2, 9]
+    );
+    assert_eq!(
+        cfg.blocks[&2].successors,
+        vec![
+            Edge {
+                target: 2,
+                kind: EdgeKind::Taken
+            },
+            Edge {
+                target: 9,
+                kind: EdgeKind::Fallthrough
+            }
+        ]
+    );
+    assert!(cfg.blocks[&9].successors.is_empty());
+    assert!(cfg.modified.is_empty() && cfg.indirect_jumps().is_empty());
+
+    // input/07 patches the target of its dispatch jump with the phase setting.
+    let input = include_bytes!("../../input/07");
+    let program = super::get_program(&input[..]).unwrap();
+    let cfg = Cfg::build(&program);
+    assert_eq!(cfg.indirect_jumps(), vec![6]);
+    assert!(cfg.written.contains(&8));
+    let dot = cfg.to_dot();
+    assert!(dot.starts_with("digraph cfg {\n"));
+    assert!(dot.contains("b0 -> indirect [style=dashed];"));
+    assert!(dot.contains("* 0006: JT #1, [0]\\l\", style=filled"));
+}
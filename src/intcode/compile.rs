@@ -0,0 +1,332 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::convert::TryFrom;
+
+use super::disasm::decode_at;
+use super::error::{VmError, VmErrorKind};
+use super::instruction::{Instruction, Param};
+use super::io::{IntcodeInput, IntcodeOutput};
+use super::memory::Memory;
+use super::snapshot::Snapshot;
+use super::vm::{Arithmetic, Limit, Status, Suspension, VM};
+
+enum Flow {
+    Next(usize),
+    Halt,
+    SuspendInput,
+}
+
+type Op = Box<
+    dyn Fn(
+        &mut Machine,
+        &mut dyn IntcodeInput,
+        &mut dyn IntcodeOutput,
+    ) -> Result<Flow, VmErrorKind>,
+>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Machine {
+    memory: Memory,
+    pc: usize,
+    relative_base: i64,
+    count: u64,
+    stale: Vec<bool>,
+}
+
+impl Machine {
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    pub fn set_memory(&mut self, addr: usize, value: i64) {
+        self.write(addr, value);
+    }
+
+    pub fn into_vm(self) -> VM {
+        VM::restore(Snapshot {
+            memory: self.memory,
+            wide: BTreeMap::new(),
+            arithmetic: Arithmetic::Checked,
+            input: VecDeque::new(),
+            pc: self.pc,
+            relative_base: self.relative_base,
+            count: self.count,
+        })
+    }
+
+    #[inline]
+    fn get(&self, param: Param) -> Result<i64, VmErrorKind> {
+        match param {
+            Param::Immediate(x) => Ok(x),
+            param => Ok(self.memory[param.address(self.relative_base)?]),
+        }
+    }
+
+    #[inline]
+    fn set(&mut self, param: Param, value: i64) -> Result<(), VmErrorKind> {
+        let addr = param.address(self.relative_base)?;
+        self.write(addr, value);
+        Ok(())
+    }
+
+    // Any compiled instruction overlapping a written word no longer matches memory.
+    #[inline]
+    fn write(&mut self, addr: usize, value: i64) {
+        self.memory.set(addr, value);
+        if addr < self.stale.len() {
+            for start in addr.saturating_sub(3)..=addr {
+                self.stale[start] = true;
+            }
+        }
+    }
+}
+
+fn jump(target: i64) -> Result<Flow, VmErrorKind> {
+    match usize::try_from(target) {
+        Ok(target) => Ok(Flow::Next(target)),
+        Err(_) => Err(VmErrorKind::JumpOutOfBounds(target)),
+    }
+}
+
+fn translate(pc: usize, inst: Instruction) -> Op {
+    let next = pc + inst.pc_offset();
+    match inst {
+        Instruction::Halt => Box::new(|_, _, _| Ok(Flow::Halt)),
+        Instruction::Add { a, b, c } => Box::new(move |m, _, _| {
+            let value = m.get(a)?.checked_add(m.get(b)?);
+            m.set(c, value.ok_or(VmErrorKind::Overflow)?)?;
+            Ok(Flow::Next(next))
+        }),
+        Instruction::Mul { a, b, c } => Box::new(move |m, _, _| {
+            let value = m.get(a)?.checked_mul(m.get(b)?);
+            m.set(c, value.ok_or(VmErrorKind::Overflow)?)?;
+            Ok(Flow::Next(next))
+        }),
+        Instruction::Input(param) => Box::new(move |m, input, _| match input.read()? {
+            Some(value) => {
+                m.set(param, value)?;
+                Ok(Flow::Next(next))
+            }
+            None => Ok(Flow::SuspendInput),
+        }),
+        Instruction::Output(param) => Box::new(move |m, _, output| {
+            output.write(m.get(param)?)?;
+            Ok(Flow::Next(next))
+        }),
+        Instruction::JumpIfTrue { cond, next: target } => Box::new(move |m, _, _| {
+            if m.get(cond)? != 0 {
+                jump(m.get(target)?)
+            } else {
+                Ok(Flow::Next(next))
+            }
+        }),
+        Instruction::JumpIfFalse { cond, next: target } => Box::new(move |m, _, _| {
+            if m.get(cond)? == 0 {
+                jump(m.get(target)?)
+            } else {
+                Ok(Flow::Next(next))
+            }
+        }),
+        Instruction::LessThan { a, b, c } => Box::new(move |m, _, _| {
+            let value = (m.get(a)? < m.get(b)?) as i64;
+            m.set(c, value)?;
+            Ok(Flow::Next(next))
+        }),
+        Instruction::Eq { a, b, c } => Box::new(move |m, _, _| {
+            let value = (m.get(a)? == m.get(b)?) as i64;
+            m.set(c, value)?;
+            Ok(Flow::Next(next))
+        }),
+        Instruction::AdjustRelativeBase(param) => Box::new(move |m, _, _| {
+            let value = m.relative_base.checked_add(m.get(param)?);
+            m.relative_base = value.ok_or(VmErrorKind::Overflow)?;
+            Ok(Flow::Next(next))
+        }),
+    }
+}
+
+// Only the default checked arithmetic is supported.
+pub struct Compiled {
+    program: Vec<i64>,
+    ops: Vec<Option<Op>>,
+    // The last translation of each address that ran an instruction differing from the program.
+    patched: RefCell<BTreeMap<usize, (Instruction, Op)>>,
+    max_instructions: Option<u64>,
+}
+
+impl Compiled {
+    pub fn new(program: &[i64]) -> Compiled {
+        let ops = (0..program.len())
+            .map(|pc| decode_at(program, pc).map(|inst| translate(pc, inst)))
+            .collect();
+        Compiled {
+            program: program.to_vec(),
+            ops,
+            patched: RefCell::new(BTreeMap::new()),
+            max_instructions: None,
+        }
+    }
+
+    pub fn set_max_instructions(&mut self, max_instructions: Option<u64>) {
+        self.max_instructions = max_instructions;
+    }
+
+    pub fn start(&self) -> Machine {
+        self.start_with_memory(Memory::dense(self.program.clone()))
+    }
+
+    // Words where the memory differs from the compiled program run through the patched path.
+    pub fn start_with_memory(&self, memory: Memory) -> Machine {
+        let mut machine = Machine {
+            memory,
+            pc: 0,
+            relative_base: 0,
+            count: 0,
+            stale: vec![false; self.program.len()],
+        };
+        for (addr, &word) in self.program.iter().enumerate() {
+            if machine.memory[addr] != word {
+                machine.write(addr, machine.memory[addr]);
+            }
+        }
+        machine
+    }
+
+    pub fn resume<I: IntcodeInput, O: IntcodeOutput>(
+        &self,
+        machine: &mut Machine,
+        mut input: I,
+        mut output: O,
+    ) -> Result<Suspension, VmError> {
+        let status = loop {
+            if self
+                .max_instructions
+                .is_some_and(|max| machine.count >= max)
+            {
+                break Status::Watchdog(Limit::Instructions);
+            }
+            let pc = machine.pc;
+            let flow = match self.ops.get(pc) {
+                Some(Some(op)) if !machine.stale[pc] => op(machine, &mut input, &mut output),
+                _ => self.run_patched(machine, &mut input, &mut output),
+            };
+            match flow {
+                Ok(Flow::Next(next)) => {
+                    machine.pc = next;
+                    machine.count += 1;
+                }
+                Ok(Flow::Halt) => break Status::Halt,
+                Ok(Flow::SuspendInput) => break Status::SuspendInput,
+                Err(kind) => {
+                    return Err(VmError {
+                        pc,
+                        opcode: machine.memory[pc],
+                        kind,
+                    })
+                }
+            }
+        };
+        Ok(Suspension {
+            status,
+            pc: machine.pc,
+            count: machine.count,
+        })
+    }
+
+    fn run_patched(
+        &self,
+        machine: &mut Machine,
+        input: &mut dyn IntcodeInput,
+        output: &mut dyn IntcodeOutput,
+    ) -> Result<Flow, VmErrorKind> {
+        let pc = machine.pc;
+        let inst = Instruction::from_slice(&machine.memory.fetch(pc))?;
+        let mut patched = self.patched.borrow_mut();
+        match patched.get(&pc) {
+            Some((cached, op)) if *cached == inst => op(machine, input, output),
+            _ => {
+                let op = translate(pc, inst);
+                let flow = op(machine, input, output);
+                patched.insert(pc, (inst, op));
+                flow
+            }
+        }
+    }
+}
+
+#[test]
+fn test() {
+    use super::get_program;
+
+    // input/05 patches its own opcode at 0006 from the input before running it.
+    let input = include_bytes!("../../input/05");
+    let program = get_program(&input[..]).unwrap();
+    let compiled = Compiled::new(&program);
+    for &id in &[1, 5] {
+        let mut machine = compiled.start();
+        let mut output = Vec::new();
+        let suspension = compiled
+            .resume(&mut machine, &[id][..], &mut output)
+            .unwrap();
+        let mut vm = VM::new(program.clone());
+        let mut expected = Vec::new();
+        let interpreted = vm.resume(&[id][..], &mut expected).unwrap();
+        assert_eq!((suspension, output), (interpreted, expected));
+        assert_eq!(machine.memory(), vm.memory());
+    }
+
+    // Suspends for input like the VM does and can be handed over to it.
+    let input = include_bytes!("../../input/07");
+    let program = get_program(&input[..]).unwrap();
+    let compiled = Compiled::new(&program);
+    let mut machine = compiled.start();
+    let suspension = compiled.resume(&mut machine, &[4][..], Vec::new()).unwrap();
+    assert_eq!(suspension.status, Status::SuspendInput);
+    let mut vm = machine.clone().into_vm();
+    let mut output = Vec::new();
+    vm.resume(&[0][..], &mut output).unwrap();
+    let mut expected = Vec::new();
+    compiled
+        .resume(&mut machine, &[0][..], &mut expected)
+        .unwrap();
+    assert_eq!(output, expected);
+
+    let mut compiled = Compiled::new(&[1105, 1, 0]);
+    compiled.set_max_instructions(Some(10));
+    let mut machine = compiled.start();
+    let suspension = compiled.resume(&mut machine, &[][..], Vec::new()).unwrap();
+    assert_eq!(suspension.status, Status::Watchdog(Limit::Instructions));
+
+    let compiled = Compiled::new(&[1101, 0, 0, 100_000_000, 99]);
+    let mut machine = compiled.start();
+    compiled.resume(&mut machine, &[][..], Vec::new()).unwrap();
+    assert_eq!(machine.memory().extent(), 5);
+    let mut machine = compiled.start_with_memory(Memory::paged(&[1101, 0, 7, 1 << 45, 99]));
+    compiled.resume(&mut machine, &[][..], Vec::new()).unwrap();
+    assert_eq!(machine.memory()[1 << 45], 7);
+    assert!(matches!(machine.memory(), Memory::Paged(_)));
+
+    // The jump at 0004 counts down its own condition, retranslating on every pass.
+    let compiled = Compiled::new(&[101, -1, 5, 5, 1105, 3, 0, 99]);
+    let mut machine = compiled.start();
+    let suspension = compiled.resume(&mut machine, &[][..], Vec::new()).unwrap();
+    let mut vm = VM::new(vec![101, -1, 5, 5, 1105, 3, 0, 99]);
+    assert_eq!(suspension, vm.resume(&[][..], Vec::new()).unwrap());
+    assert_eq!(compiled.patched.borrow().len(), 1);
+
+    let compiled = Compiled::new(&[1105, 1, -1]);
+    let error = compiled.resume(&mut compiled.start(), &[][..], Vec::new());
+    let expected = VM::new(vec![1105, 1, -1]).resume(&[][..], Vec::new());
+    assert_eq!(
+        error.unwrap_err().to_string(),
+        expected.unwrap_err().to_string()
+    );
+}
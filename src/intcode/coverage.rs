@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use super::disasm::disassemble;
+use super::instruction::Instruction;
+
+const EXECUTED: u8 = 1;
+const OPERAND: u8 = 2;
+const READ: u8 = 4;
+const WRITTEN: u8 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Use {
+    Executed,
+    Data,
+    Untouched,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Coverage {
+    flags: BTreeMap<usize, u8>,
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    pub fn record(&mut self, pc: usize, inst: &Instruction, reads: &[usize], write: Option<usize>) {
+        self.mark(pc, EXECUTED);
+        for addr in pc + 1..pc + inst.pc_offset() {
+            self.mark(addr, OPERAND);
+        }
+        for &addr in reads {
+            self.mark(addr, READ);
+        }
+        if let Some(addr) = write {
+            self.mark(addr, WRITTEN);
+        }
+    }
+
+    fn mark(&mut self, addr: usize, flag: u8) {
+        *self.flags.entry(addr).or_insert(0) |= flag;
+    }
+
+    pub fn merge(&mut self, other: &Coverage) {
+        for (&addr, &flags) in &other.flags {
+            self.mark(addr, flags);
+        }
+    }
+
+    // A word that was both run and treated as data, like a parameter the program patches,
+    // counts as executed.
+    pub fn classify(&self, addr: usize) -> Use {
+        match self.flags.get(&addr).copied().unwrap_or(0) {
+            0 => Use::Untouched,
+            flags if flags & (EXECUTED | OPERAND) != 0 => Use::Executed,
+            _ => Use::Data,
+        }
+    }
+
+    pub fn was_read(&self, addr: usize) -> bool {
+        self.flags.get(&addr).is_some_and(|flags| flags & READ != 0)
+    }
+
+    pub fn was_written(&self, addr: usize) -> bool {
+        self.flags
+            .get(&addr)
+            .is_some_and(|flags| flags & WRITTEN != 0)
+    }
+
+    // Only words of the program image count as untouched; anything past it is open memory.
+    pub fn summary(&self, len: usize) -> (usize, usize, usize) {
+        let mut counts = (0, 0, 0);
+        for &addr in self.flags.keys() {
+            match self.classify(addr) {
+                Use::Executed => counts.0 += 1,
+                _ => counts.1 += 1,
+            }
+        }
+        counts.2 = len - self.flags.range(..len).count();
+        counts
+    }
+
+    // Each line is marked with `>` if its first word was executed, `r`/`w` if it was only
+    // used as data and `.` if nothing touched it.
+    pub fn listing(&self, program: &[i64]) -> String {
+        let mut result = String::new();
+        let (executed, data, untouched) = self.summary(program.len());
+        writeln!(
+            result,
+            "; {} executed, {} data, {} untouched",
+            executed, data, untouched
+        )
+        .unwrap();
+        for line in disassemble(program) {
+            let marker = match self.classify(line.addr) {
+                Use::Executed => "> ",
+                Use::Data => match (self.was_read(line.addr), self.was_written(line.addr)) {
+                    (true, true) => "rw",
+                    (true, false) => "r ",
+                    _ => " w",
+                },
+                Use::Untouched => ". ",
+            };
+            writeln!(result, "{} {}", marker, line).unwrap();
+        }
+        result
+    }
+}
+
+#[test]
+fn test() {
+    use super::vm::VM;
+
+    let input = include_bytes!("../../input/02");
+    let program = super::get_program(&input[..]).unwrap();
+    let mut total = Coverage::new();
+    for noun in 0..3 {
+        for verb in 0..3 {
+            let mut program = program.clone();
+            program[1] = noun;
+            program[2] = verb;
+            let mut vm = VM::new(program);
+            vm.set_coverage(true);
+            vm.resume(&[][..], Vec::new()).unwrap();
+            total.merge(vm.coverage().unwrap());
+        }
+    }
+    assert_eq!(total.classify(0), Use::Executed);
+    assert_eq!(total.classify(1), Use::Executed);
+    assert!(total.was_read(0) && total.was_read(1) && total.was_read(2));
+    assert!(total.was_written(0) && total.was_written(3));
+    let (executed, data, untouched) = total.summary(program.len());
+    assert_eq!(executed + data + untouched, program.len());
+    assert!(untouched > 0);
+
+    let program = [1101, 1, 2, 1_000_000_000, 99];
+    let mut vm = VM::with_memory(super::memory::Memory::paged(&program));
+    vm.set_coverage(true);
+    vm.resume(&[][..], Vec::new()).unwrap();
+    let coverage = vm.coverage().unwrap();
+    assert_eq!(coverage.summary(5), (5, 1, 0));
+
+    let program = vec![1101, 1, 2, 5, 99, 0, 7];
+    let mut vm = VM::new(program.clone());
+    vm.set_coverage(true);
+    vm.resume(&[][..], Vec::new()).unwrap();
+    assert_eq!(
+        vm.coverage().unwrap().listing(&program),
+        "\
+; 5 executed, 1 data, 1 untouched
+>  0000: ADD #1, #2 -> [5]
+>  0004: HLT
+ w 0005: .data 0
+.  0006: .data 7
+"
+    );
+}
@@ -0,0 +1,870 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write};
+
+use super::cfg::{Block, Cfg};
+use super::instruction::{Instruction, Param};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Add,
+    Mul,
+    Lt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Op {
+    fn symbol(self) -> &'static str {
+        match self {
+            Op::Add => "+",
+            Op::Mul => "*",
+            Op::Lt => "<",
+            Op::Ge => ">=",
+            Op::Eq => "==",
+            Op::Ne => "!=",
+        }
+    }
+
+    fn apply(self, a: i64, b: i64) -> Option<i64> {
+        match self {
+            Op::Add => a.checked_add(b),
+            Op::Mul => a.checked_mul(b),
+            Op::Lt => Some((a < b) as i64),
+            Op::Ge => Some((a >= b) as i64),
+            Op::Eq => Some((a == b) as i64),
+            Op::Ne => Some((a != b) as i64),
+        }
+    }
+
+    fn precedence(self) -> u8 {
+        match self {
+            Op::Mul => 3,
+            Op::Add => 2,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Num(i64),
+    Var(usize),
+    Mem(Box<Expr>),
+    Rel(i64),
+    Base,
+    Input,
+    Bin(Op, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    // Operations on constants are folded, unless they would overflow.
+    fn bin(op: Op, a: Expr, b: Expr) -> Expr {
+        if let (Expr::Num(x), Expr::Num(y)) = (&a, &b) {
+            if let Some(value) = op.apply(*x, *y) {
+                return Expr::Num(value);
+            }
+        }
+        Expr::Bin(op, Box::new(a), Box::new(b))
+    }
+
+    // The condition under which a jump on this value is taken, written as a comparison.
+    fn truthy(self) -> Expr {
+        match self {
+            Expr::Bin(Op::Lt, ..) | Expr::Bin(Op::Eq, ..) => self,
+            _ => Expr::bin(Op::Ne, self, Expr::Num(0)),
+        }
+    }
+
+    fn negate(self) -> Expr {
+        match self {
+            Expr::Bin(op, a, b) => {
+                let op = match op {
+                    Op::Lt => Op::Ge,
+                    Op::Ge => Op::Lt,
+                    Op::Eq => Op::Ne,
+                    Op::Ne => Op::Eq,
+                    op => return Expr::bin(Op::Eq, Expr::Bin(op, a, b), Expr::Num(0)),
+                };
+                Expr::Bin(op, a, b)
+            }
+            expr => Expr::bin(Op::Eq, expr, Expr::Num(0)),
+        }
+    }
+
+    fn uses(&self, var: usize) -> usize {
+        match self {
+            Expr::Var(v) => (*v == var) as usize,
+            Expr::Mem(index) => index.uses(var),
+            Expr::Bin(_, a, b) => a.uses(var) + b.uses(var),
+            _ => 0,
+        }
+    }
+
+    fn vars(&self, vars: &mut BTreeSet<usize>) {
+        match self {
+            Expr::Var(v) => {
+                vars.insert(*v);
+            }
+            Expr::Mem(index) => index.vars(vars),
+            Expr::Bin(_, a, b) => {
+                a.vars(vars);
+                b.vars(vars);
+            }
+            _ => {}
+        }
+    }
+
+    fn has_input(&self) -> bool {
+        match self {
+            Expr::Input => true,
+            Expr::Mem(index) => index.has_input(),
+            Expr::Bin(_, a, b) => a.has_input() || b.has_input(),
+            _ => false,
+        }
+    }
+
+    // Reads memory at an address only known at runtime, which may be any variable.
+    fn dynamic(&self) -> bool {
+        match self {
+            Expr::Rel(_) => true,
+            Expr::Mem(index) => !matches!(**index, Expr::Num(_)),
+            Expr::Bin(_, a, b) => a.dynamic() || b.dynamic(),
+            _ => false,
+        }
+    }
+
+    fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Expr::Bin(Op::Lt, ..)
+                | Expr::Bin(Op::Ge, ..)
+                | Expr::Bin(Op::Eq, ..)
+                | Expr::Bin(Op::Ne, ..)
+        )
+    }
+
+    // Constants fold again, and testing a substituted comparison against zero collapses into the
+    // comparison itself.
+    fn substitute(&mut self, var: usize, value: &Expr) {
+        match self {
+            Expr::Var(v) if *v == var => *self = value.clone(),
+            Expr::Mem(index) => index.substitute(var, value),
+            Expr::Bin(op, a, b) => {
+                a.substitute(var, value);
+                b.substitute(var, value);
+                if let (Expr::Num(_), Expr::Num(_)) = (&**a, &**b) {
+                    *self = Expr::bin(*op, (**a).clone(), (**b).clone());
+                } else if a.is_comparison() && **b == Expr::Num(0) {
+                    match op {
+                        Op::Ne => *self = (**a).clone(),
+                        Op::Eq => *self = (**a).clone().negate(),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn fmt_with(&self, f: &mut fmt::Formatter, parent: u8) -> fmt::Result {
+        match self {
+            Expr::Num(x) => write!(f, "{}", x),
+            Expr::Var(v) => write!(f, "v{}", v),
+            Expr::Mem(index) => write!(f, "mem[{}]", index),
+            Expr::Rel(0) => write!(f, "mem[rb]"),
+            Expr::Rel(offset) if *offset < 0 => write!(f, "mem[rb - {}]", -(*offset as i128)),
+            Expr::Rel(offset) => write!(f, "mem[rb + {}]", offset),
+            Expr::Base => write!(f, "rb"),
+            Expr::Input => write!(f, "input()"),
+            Expr::Bin(op, a, b) => {
+                let precedence = op.precedence();
+                if precedence < parent {
+                    write!(f, "(")?;
+                }
+                a.fmt_with(f, precedence)?;
+                match (op, &**b) {
+                    (Op::Add, Expr::Num(x)) if *x < 0 => write!(f, " - {}", -(*x as i128))?,
+                    _ => {
+                        write!(f, " {} ", op.symbol())?;
+                        b.fmt_with(f, precedence + 1)?;
+                    }
+                }
+                if precedence < parent {
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_with(f, 0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    Static(usize),
+    Dynamic(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Simple {
+    Assign(Expr, Expr),
+    Output(Expr),
+    AdjustBase(Expr),
+    // The condition is the one under which the jump is taken.
+    Jump(Option<Expr>, Target),
+    Halt,
+    Nop,
+}
+
+impl Simple {
+    fn reads(&self) -> Vec<&Expr> {
+        match self {
+            Simple::Assign(Expr::Mem(index), value) => vec![index, value],
+            Simple::Assign(_, value) | Simple::Output(value) | Simple::AdjustBase(value) => {
+                vec![value]
+            }
+            Simple::Jump(cond, target) => {
+                let mut reads: Vec<&Expr> = cond.iter().collect();
+                if let Target::Dynamic(target) = target {
+                    reads.push(target);
+                }
+                reads
+            }
+            Simple::Halt | Simple::Nop => vec![],
+        }
+    }
+
+    fn reads_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Simple::Assign(Expr::Mem(index), value) => vec![index, value],
+            Simple::Assign(_, value) | Simple::Output(value) | Simple::AdjustBase(value) => {
+                vec![value]
+            }
+            Simple::Jump(cond, target) => {
+                let mut reads: Vec<&mut Expr> = cond.iter_mut().collect();
+                if let Target::Dynamic(target) = target {
+                    reads.push(target);
+                }
+                reads
+            }
+            Simple::Halt | Simple::Nop => vec![],
+        }
+    }
+
+    fn defines(&self) -> Option<usize> {
+        match self {
+            Simple::Assign(Expr::Var(v), _) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn uses(&self, var: usize) -> usize {
+        self.reads().iter().map(|expr| expr.uses(var)).sum()
+    }
+
+    fn has_input(&self) -> bool {
+        self.reads().iter().any(|expr| expr.has_input())
+    }
+
+    fn dynamic(&self) -> bool {
+        self.reads().iter().any(|expr| expr.dynamic())
+    }
+
+    fn falls_through(&self) -> bool {
+        !matches!(self, Simple::Halt | Simple::Jump(None, _))
+    }
+}
+
+impl fmt::Display for Simple {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let jump = match self {
+            Simple::Assign(place, value) => return write!(f, "{} = {};", place, value),
+            Simple::Output(value) => return write!(f, "output({});", value),
+            Simple::AdjustBase(value) => return write!(f, "rb += {};", value),
+            Simple::Halt => return write!(f, "halt;"),
+            Simple::Nop => return Ok(()),
+            Simple::Jump(_, Target::Static(target)) => format!("goto L{:04};", target),
+            Simple::Jump(_, Target::Dynamic(target)) => format!("goto *{};", target),
+        };
+        match self {
+            Simple::Jump(Some(cond), _) => write!(f, "if ({}) {}", cond, jump),
+            _ => write!(f, "{}", jump),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Item {
+    addr: usize,
+    next: usize,
+    simple: Simple,
+    patched: bool,
+    // Variables that may still be read after this statement.
+    live: BTreeSet<usize>,
+}
+
+impl Item {
+    // The target of a jump that can be followed statically.
+    fn jump(&self) -> Option<usize> {
+        match self.simple {
+            Simple::Jump(_, Target::Static(target)) if !self.patched => Some(target),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Branch {
+    Goto(usize),
+    Break,
+    Continue,
+}
+
+#[derive(Debug, Clone)]
+enum Stmt {
+    Simple(Item),
+    Branch(usize, Option<Expr>, Branch),
+    If(usize, Expr, Vec<Stmt>, Vec<Stmt>),
+    While(usize, Expr, Vec<Stmt>),
+    DoWhile(usize, Vec<Stmt>, Expr),
+    Loop(usize, Vec<Stmt>),
+}
+
+impl Stmt {
+    fn addr(&self) -> usize {
+        match self {
+            Stmt::Simple(item) => item.addr,
+            Stmt::Branch(addr, ..)
+            | Stmt::If(addr, ..)
+            | Stmt::While(addr, ..)
+            | Stmt::DoWhile(addr, ..)
+            | Stmt::Loop(addr, ..) => *addr,
+        }
+    }
+
+    fn gotos(&self, labels: &mut BTreeSet<usize>) {
+        let nested: Vec<&Vec<Stmt>> = match self {
+            Stmt::Simple(Item {
+                simple: Simple::Jump(_, Target::Static(target)),
+                ..
+            })
+            | Stmt::Branch(_, _, Branch::Goto(target)) => {
+                labels.insert(*target);
+                return;
+            }
+            Stmt::If(_, _, then, otherwise) => vec![then, otherwise],
+            Stmt::While(_, _, body) | Stmt::DoWhile(_, body, _) | Stmt::Loop(_, body) => {
+                vec![body]
+            }
+            _ => return,
+        };
+        for stmt in nested.into_iter().flatten() {
+            stmt.gotos(labels);
+        }
+    }
+}
+
+struct Decompiler<'a> {
+    program: &'a [i64],
+    cfg: Cfg,
+    code: BTreeSet<usize>,
+    variables: BTreeSet<usize>,
+}
+
+impl<'a> Decompiler<'a> {
+    fn new(program: &'a [i64], entries: &[usize]) -> Decompiler<'a> {
+        let cfg = Cfg::build_from(program, entries);
+        let mut code = BTreeSet::new();
+        let mut variables = BTreeSet::new();
+        for block in cfg.blocks.values() {
+            for &(addr, inst) in &block.instructions {
+                code.extend(addr..addr + inst.pc_offset());
+                for param in inst.params() {
+                    if let Param::Position(addr) = param {
+                        variables.insert(addr);
+                    }
+                }
+            }
+        }
+        let variables = variables.difference(&code).copied().collect();
+        Decompiler {
+            program,
+            cfg,
+            code,
+            variables,
+        }
+    }
+
+    // A word the program overwrites no longer holds what the listing shows, so the operand
+    // it encodes is read from memory instead.
+    fn operand(&self, word: usize, param: Param) -> Expr {
+        let stored = || Expr::Mem(Box::new(Expr::Num(word as i64)));
+        let patched = self.cfg.written.contains(&word);
+        match param {
+            Param::Immediate(_) if patched => stored(),
+            Param::Immediate(x) => Expr::Num(x),
+            Param::Position(_) if patched => Expr::Mem(Box::new(stored())),
+            Param::Position(addr) if self.code.contains(&addr) => {
+                Expr::Mem(Box::new(Expr::Num(addr as i64)))
+            }
+            Param::Position(addr) => Expr::Var(addr),
+            Param::Relative(_) if patched => {
+                Expr::Mem(Box::new(Expr::bin(Op::Add, Expr::Base, stored())))
+            }
+            Param::Relative(offset) => Expr::Rel(offset),
+        }
+    }
+
+    fn translate(&self, addr: usize, inst: Instruction) -> Simple {
+        let operand = |idx: usize, param: Param| self.operand(addr + 1 + idx, param);
+        let assign = |op: Op, a: Param, b: Param, c: Param| {
+            let value = Expr::bin(op, operand(0, a), operand(1, b));
+            Simple::Assign(operand(2, c), value)
+        };
+        let jump = |cond: Param, next: Param, if_true: bool| {
+            let cond = match operand(0, cond) {
+                Expr::Num(x) if (x != 0) == if_true => None,
+                Expr::Num(_) => return Simple::Nop,
+                cond if if_true => Some(cond.truthy()),
+                cond => Some(cond.truthy().negate()),
+            };
+            let target = match operand(1, next) {
+                Expr::Num(x) if x >= 0 => Target::Static(x as usize),
+                target => Target::Dynamic(target),
+            };
+            Simple::Jump(cond, target)
+        };
+        match inst {
+            Instruction::Halt => Simple::Halt,
+            Instruction::Add { a, b, c } => assign(Op::Add, a, b, c),
+            Instruction::Mul { a, b, c } => assign(Op::Mul, a, b, c),
+            Instruction::LessThan { a, b, c } => assign(Op::Lt, a, b, c),
+            Instruction::Eq { a, b, c } => assign(Op::Eq, a, b, c),
+            Instruction::Input(param) => Simple::Assign(operand(0, param), Expr::Input),
+            Instruction::Output(param) => Simple::Output(operand(0, param)),
+            Instruction::AdjustRelativeBase(param) => Simple::AdjustBase(operand(0, param)),
+            Instruction::JumpIfTrue { cond, next } => jump(cond, next, true),
+            Instruction::JumpIfFalse { cond, next } => jump(cond, next, false),
+        }
+    }
+
+    fn reads(&self, simple: &Simple) -> BTreeSet<usize> {
+        let mut vars = BTreeSet::new();
+        let unknown = matches!(simple, Simple::Jump(_, Target::Dynamic(_)));
+        if unknown || simple.dynamic() {
+            return self.variables.clone();
+        }
+        for expr in simple.reads() {
+            expr.vars(&mut vars);
+        }
+        vars
+    }
+
+    // Every variable is assumed live wherever control leaves what the CFG can follow. Only
+    // I/O counts as observable, so nothing is live at a halt; stores to code are never
+    // folded away, which keeps results left in memory like day 2's.
+    fn items(&self) -> Vec<Item> {
+        let mut blocks: BTreeMap<usize, Vec<Item>> = BTreeMap::new();
+        for (&start, block) in &self.cfg.blocks {
+            let items = block.instructions.iter().map(|&(addr, inst)| Item {
+                addr,
+                next: addr + inst.pc_offset(),
+                simple: self.translate(addr, inst),
+                patched: self.cfg.modified.contains(&addr),
+                live: BTreeSet::new(),
+            });
+            blocks.insert(start, items.collect());
+        }
+
+        let mut live_in: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+        loop {
+            let mut changed = false;
+            for (start, block) in self.cfg.blocks.iter().rev() {
+                let mut live = self.live_out(block, &live_in);
+                for item in blocks[start].iter().rev() {
+                    if let Some(var) = item.simple.defines() {
+                        live.remove(&var);
+                    }
+                    live.extend(self.reads(&item.simple));
+                }
+                if live_in.get(start) != Some(&live) {
+                    live_in.insert(*start, live);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let mut items = Vec::new();
+        for (start, block) in blocks.iter_mut() {
+            let mut live = self.live_out(&self.cfg.blocks[start], &live_in);
+            for item in block.iter_mut().rev() {
+                item.live = live.clone();
+                if let Some(var) = item.simple.defines() {
+                    live.remove(&var);
+                }
+                live.extend(self.reads(&item.simple));
+            }
+
+            let mut folded: Vec<Item> = Vec::new();
+            for item in block.drain(..) {
+                folded.push(item);
+                while fold(&mut folded) {}
+            }
+            items.extend(folded);
+        }
+        items
+    }
+
+    fn live_out(
+        &self,
+        block: &Block,
+        live_in: &BTreeMap<usize, BTreeSet<usize>>,
+    ) -> BTreeSet<usize> {
+        let unknown = block
+            .successors
+            .iter()
+            .any(|edge| !self.cfg.blocks.contains_key(&edge.target));
+        if block.indirect || unknown {
+            return self.variables.clone();
+        }
+        let mut live = BTreeSet::new();
+        for edge in &block.successors {
+            live.extend(live_in.get(&edge.target).into_iter().flatten().copied());
+        }
+        live
+    }
+
+    // Recovers loops and conditionals from the jumps in `items`, which `follow` comes right
+    // after. A loop starts where the last jump back to it in `items` lands; jumps that fit
+    // no pattern become gotos.
+    fn structure(
+        &self,
+        items: &[Item],
+        follow: Option<usize>,
+        innermost: Option<(usize, usize)>,
+    ) -> Vec<Stmt> {
+        let index = |addr: usize| match items.iter().position(|item| item.addr == addr) {
+            Some(idx) => Some(idx),
+            None if Some(addr) == follow => Some(items.len()),
+            None => None,
+        };
+
+        let mut stmts: Vec<Stmt> = Vec::new();
+        let mut idx = 0;
+        while idx < items.len() {
+            let item = &items[idx];
+            let head = item.addr;
+            let back = match innermost {
+                Some((start, _)) if start == head => None,
+                _ => items[idx..]
+                    .iter()
+                    .rposition(|other| other.jump() == Some(head)),
+            };
+            if let Some(back) = back.map(|back| idx + back) {
+                let exit = items[back].next;
+                let lp = Some((head, exit));
+                let follow = Some(items[back].addr);
+                let stmt = match (&items[back].simple, &item.simple) {
+                    (Simple::Jump(Some(cond), _), _) => {
+                        let body = self.structure(&items[idx..back], follow, lp);
+                        Stmt::DoWhile(head, body, cond.clone())
+                    }
+                    (_, Simple::Jump(Some(cond), _)) if item.jump() == Some(exit) => {
+                        let body = self.structure(&items[idx + 1..back], follow, lp);
+                        Stmt::While(head, cond.clone().negate(), body)
+                    }
+                    _ => Stmt::Loop(head, self.structure(&items[idx..back], follow, lp)),
+                };
+                stmts.push(stmt);
+                self.fall(items, back, follow, &mut stmts);
+                idx = back + 1;
+                continue;
+            }
+
+            let (cond, target) = match (&item.simple, item.jump()) {
+                (Simple::Jump(cond, _), Some(target)) => (cond.clone(), target),
+                _ => {
+                    stmts.push(Stmt::Simple(item.clone()));
+                    self.fall(items, idx, follow, &mut stmts);
+                    idx += 1;
+                    continue;
+                }
+            };
+            let branch = match innermost {
+                Some((_, exit)) if target == exit => Branch::Break,
+                Some((head, _)) if target == head => Branch::Continue,
+                _ => Branch::Goto(target),
+            };
+            let end = index(target).filter(|&end| end > idx + 1);
+            match (branch, cond, end) {
+                (Branch::Goto(_), Some(cond), Some(end)) if target > item.addr => {
+                    let body = &items[idx + 1..end];
+                    let last = body.last().unwrap();
+                    let skip = last.jump().filter(|&skip| {
+                        skip > target && matches!(last.simple, Simple::Jump(None, _))
+                    });
+                    if let Some(other) = skip.and_then(index) {
+                        let then = &body[..body.len() - 1];
+                        let then = self.structure(then, Some(last.addr), innermost);
+                        let follow = items.get(other).map(|item| item.addr).or(follow);
+                        let otherwise = self.structure(&items[end..other], follow, innermost);
+                        stmts.push(Stmt::If(item.addr, cond.negate(), then, otherwise));
+                        idx = other;
+                    } else {
+                        let then = self.structure(body, Some(target), innermost);
+                        stmts.push(Stmt::If(item.addr, cond.negate(), then, vec![]));
+                        idx = end;
+                    }
+                }
+                (branch, cond, _) => {
+                    stmts.push(Stmt::Branch(item.addr, cond, branch));
+                    self.fall(items, idx, follow, &mut stmts);
+                    idx += 1;
+                }
+            }
+        }
+        stmts
+    }
+
+    // Makes falling through to anything but the next statement explicit.
+    fn fall(&self, items: &[Item], idx: usize, follow: Option<usize>, stmts: &mut Vec<Stmt>) {
+        let item = &items[idx];
+        let next = items.get(idx + 1).map(|item| item.addr).or(follow);
+        if item.simple.falls_through() && next != Some(item.next) {
+            stmts.push(Stmt::Branch(item.addr, None, Branch::Goto(item.next)));
+        }
+    }
+
+    fn print(&self, stmts: &[Stmt], depth: usize, labels: &BTreeSet<usize>, out: &mut String) {
+        let pad = "    ".repeat(depth);
+        for stmt in stmts {
+            if labels.contains(&stmt.addr()) {
+                writeln!(out, "{}L{:04}:", pad, stmt.addr()).unwrap();
+            }
+            match stmt {
+                Stmt::Simple(item) => {
+                    let text = item.simple.to_string();
+                    if text.is_empty() {
+                        continue;
+                    }
+                    let note = if item.patched {
+                        "  // patched at runtime"
+                    } else {
+                        ""
+                    };
+                    writeln!(out, "{}{}{}", pad, text, note).unwrap();
+                }
+                Stmt::Branch(_, cond, branch) => {
+                    let branch = match branch {
+                        Branch::Goto(target) => format!("goto L{:04};", target),
+                        Branch::Break => "break;".to_string(),
+                        Branch::Continue => "continue;".to_string(),
+                    };
+                    match cond {
+                        Some(cond) => writeln!(out, "{}if ({}) {}", pad, cond, branch).unwrap(),
+                        None => writeln!(out, "{}{}", pad, branch).unwrap(),
+                    }
+                }
+                Stmt::If(_, cond, then, otherwise) => {
+                    writeln!(out, "{}if ({}) {{", pad, cond).unwrap();
+                    self.print(then, depth + 1, labels, out);
+                    if !otherwise.is_empty() {
+                        writeln!(out, "{}}} else {{", pad).unwrap();
+                        self.print(otherwise, depth + 1, labels, out);
+                    }
+                    writeln!(out, "{}}}", pad).unwrap();
+                }
+                Stmt::While(_, cond, body) => {
+                    writeln!(out, "{}while ({}) {{", pad, cond).unwrap();
+                    self.print(body, depth + 1, labels, out);
+                    writeln!(out, "{}}}", pad).unwrap();
+                }
+                Stmt::DoWhile(_, body, cond) => {
+                    writeln!(out, "{}do {{", pad).unwrap();
+                    self.print(body, depth + 1, labels, out);
+                    writeln!(out, "{}}} while ({});", pad, cond).unwrap();
+                }
+                Stmt::Loop(_, body) => {
+                    writeln!(out, "{}loop {{", pad).unwrap();
+                    self.print(body, depth + 1, labels, out);
+                    writeln!(out, "{}}}", pad).unwrap();
+                }
+            }
+        }
+    }
+}
+
+// Moves a variable's value into the one following statement that reads it, if nothing else
+// can read it afterwards. Patched instructions and reads through pointers are left alone.
+fn fold(items: &mut Vec<Item>) -> bool {
+    let (first, second) = match &items[..] {
+        [.., first, second] => (first, second),
+        _ => return false,
+    };
+    let (var, value) = match &first.simple {
+        Simple::Assign(Expr::Var(var), value) => (*var, value.clone()),
+        _ => return false,
+    };
+    let dead = !second.live.contains(&var) || second.simple.defines() == Some(var);
+    if first.patched
+        || second.patched
+        || second.simple.dynamic()
+        || second.simple.uses(var) != 1
+        || (value.has_input() && second.simple.has_input())
+        || !dead
+    {
+        return false;
+    }
+    let mut second = items.pop().unwrap();
+    let first = items.pop().unwrap();
+    for expr in second.simple.reads_mut() {
+        expr.substitute(var, &value);
+    }
+    second.addr = first.addr;
+    items.push(second);
+    true
+}
+
+pub fn decompile(program: &[i64]) -> String {
+    decompile_from(program, &[0])
+}
+
+pub fn decompile_from(program: &[i64], entries: &[usize]) -> String {
+    let decompiler = Decompiler::new(program, entries);
+    let items = decompiler.items();
+    let mut variables = BTreeSet::new();
+    for item in &items {
+        variables.extend(item.simple.defines());
+        for expr in item.simple.reads() {
+            expr.vars(&mut variables);
+        }
+    }
+    let stmts = decompiler.structure(&items, None, None);
+    let mut labels: BTreeSet<usize> = entries.iter().copied().filter(|&addr| addr != 0).collect();
+    for stmt in &stmts {
+        stmt.gotos(&mut labels);
+    }
+
+    let mut out = String::new();
+    for &var in &variables {
+        let value = decompiler.program.get(var).copied().unwrap_or(0);
+        writeln!(out, "var v{} = {};", var, value).unwrap();
+    }
+    if !variables.is_empty() {
+        out.push('\n');
+    }
+    decompiler.print(&stmts, 0, &labels, &mut out);
+    for addr in &decompiler.cfg.undecodable {
+        writeln!(out, "L{:04}: // not decodable", addr).unwrap();
+    }
+    out
+}
+
+#[test]
+fn test() {
+    use super::asm::assemble;
+
+    let source = "
+                in -> [n]
+                add #0, #0 -> [sum]
+        top:    lt #0, [n] -> [more]
+                jf [more], #done
+                add [sum], [n] -> [sum]
+                add [n], #-1 -> [n]
+                jt #1, #top
+        done:   eq [sum], #10 -> [ten]
+                jt [ten], #yes
+                out [sum]
+                jt #1, #end
+        yes:    out #10
+        end:    hlt
+        n:      .data 0
+        sum:    .data 0
+        more:   .data 0
+        ten:    .data 0
+    ";
+    let program = assemble(source).unwrap().program;
+    assert_eq!(
+        decompile(&program),
+        "\
+var v39 = 0;
+var v40 = 0;
+
+v39 = input();
+v40 = 0;
+while (0 < v39) {
+    v40 = v40 + v39;
+    v39 = v39 - 1;
+}
+if (v40 != 10) {
+    output(v40);
+} else {
+    output(10);
+}
+halt;
+"
+    );
+
+    let source = "
+                in -> [n]
+        again:  out [n]
+                add [n], #-2 -> [n]
+                lt [n], #0 -> [neg]
+                jt [neg], #done
+                eq [n], #5 -> [five]
+                jt [five], #skip
+                out #1
+        skip:   jt [n], #again
+        done:   out [n]
+                hlt
+        n:      .data 0
+        neg:    .data 0
+        five:   .data 0
+    ";
+    let program = assemble(source).unwrap().program;
+    assert_eq!(
+        decompile(&program),
+        "\
+var v30 = 0;
+
+v30 = input();
+do {
+    output(v30);
+    v30 = v30 - 2;
+    if (v30 < 0) break;
+    if (v30 != 5) {
+        output(1);
+    }
+} while (v30 != 0);
+output(v30);
+halt;
+"
+    );
+
+    // input/07 dispatches on the phase through a jump table at 0010.
+    let input = include_bytes!("../../input/07");
+    let program = super::get_program(&input[..]).unwrap();
+    let mut entries = vec![0];
+    entries.extend(program[10..20].iter().map(|&addr| addr as usize));
+    let code = decompile_from(&program, &entries);
+    assert!(code.starts_with(
+        "\
+mem[8] = input();
+mem[8] = mem[8] + 10;
+goto *mem[mem[8]];  // patched at runtime
+L0021:
+output((5 * (2 + input()) + 2) * 5);
+halt;
+"
+    ));
+    assert!(code.contains("L0098:\noutput(input() * 2);\noutput(1 + input());\n"));
+}
@@ -0,0 +1,89 @@
+use std::fmt;
+
+use super::instruction::Instruction;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decoded {
+    Instruction(Instruction),
+    Data(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Line {
+    pub addr: usize,
+    pub decoded: Decoded,
+}
+
+impl Line {
+    pub fn size(&self) -> usize {
+        match &self.decoded {
+            Decoded::Instruction(inst) => inst.pc_offset(),
+            Decoded::Data(_) => 1,
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.decoded {
+            Decoded::Instruction(inst) => write!(f, "{:04}: {}", self.addr, inst),
+            Decoded::Data(word) => write!(f, "{:04}: .data {}", self.addr, word),
+        }
+    }
+}
+
+// Words that don't encode back exactly, like stray mode digits, stay data.
+pub fn decode_at(program: &[i64], addr: usize) -> Option<Instruction> {
+    let slice = program.get(addr..)?;
+    let inst = Instruction::from_slice(slice).ok()?;
+    let words = inst.encode();
+    if slice.get(..words.len())? == &words[..] {
+        Some(inst)
+    } else {
+        None
+    }
+}
+
+pub fn disassemble(program: &[i64]) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut addr = 0;
+    while addr < program.len() {
+        let decoded = match decode_at(program, addr) {
+            Some(inst) => Decoded::Instruction(inst),
+            None => Decoded::Data(program[addr]),
+        };
+        let line = Line { addr, decoded };
+        addr += line.size();
+        lines.push(line);
+    }
+    lines
+}
+
+pub fn listing(program: &[i64]) -> String {
+    let mut result = String::new();
+    for line in disassemble(program) {
+        result.push_str(&line.to_string());
+        result.push('\n');
+    }
+    result
+}
+
+#[test]
+fn test() {
+    let program = vec![
+        3, 225, 1101, 225, 6, 6, 21209, -1, 1099, 104, 0, 1105, 1, 0, 99,
+    ];
+    assert_eq!(
+        listing(&program),
+        "\
+0000: IN -> [225]
+0002: ADD #225, #6 -> [6]
+0006: .data 21209
+0007: .data -1
+0008: .data 1099
+0009: OUT #0
+0011: JT #1, #0
+0014: HLT
+"
+    );
+}
@@ -0,0 +1,60 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VmErrorKind {
+    UnknownOpcode,
+    BadParameterMode(u64),
+    ImmediateWrite,
+    NegativeAddress(i64),
+    JumpOutOfBounds(i64),
+    InputExhausted,
+    BadInput(String),
+    Io(String),
+    Overflow,
+}
+
+impl fmt::Display for VmErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VmErrorKind::UnknownOpcode => write!(f, "unknown opcode"),
+            VmErrorKind::BadParameterMode(mode) => write!(f, "bad parameter mode {}", mode),
+            VmErrorKind::ImmediateWrite => write!(f, "write target in immediate mode"),
+            VmErrorKind::NegativeAddress(addr) => write!(f, "negative address {}", addr),
+            VmErrorKind::JumpOutOfBounds(target) => write!(f, "jump out of bounds to {}", target),
+            VmErrorKind::InputExhausted => write!(f, "input exhausted"),
+            VmErrorKind::BadInput(line) => write!(f, "bad input {:?}", line),
+            VmErrorKind::Io(err) => write!(f, "i/o error: {}", err),
+            VmErrorKind::Overflow => write!(f, "arithmetic overflow"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmError {
+    pub pc: usize,
+    pub opcode: i64,
+    pub kind: VmErrorKind,
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at pc {} (opcode {})",
+            self.kind, self.pc, self.opcode
+        )
+    }
+}
+
+impl Error for VmError {}
+
+#[test]
+fn test() {
+    let error = VmError {
+        pc: 12,
+        opcode: 42,
+        kind: VmErrorKind::UnknownOpcode,
+    };
+    assert_eq!(error.to_string(), "unknown opcode at pc 12 (opcode 42)");
+}
@@ -0,0 +1,147 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io::prelude::*;
+
+use super::asm::Assembly;
+use super::memory::Memory;
+use super::snapshot::Snapshot;
+use super::varint::{read_varint, write_varint};
+use super::vm::{Arithmetic, VM};
+use super::Result;
+
+pub const MAGIC: &[u8] = b"ICPG";
+const VERSION: i128 = 1;
+
+// The magic, then varints: version, entry, symbols as (length, name, address), and words.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub entry: usize,
+    pub symbols: BTreeMap<String, usize>,
+    pub program: Vec<i64>,
+}
+
+impl Image {
+    pub fn new(program: Vec<i64>) -> Image {
+        Image {
+            entry: 0,
+            symbols: BTreeMap::new(),
+            program,
+        }
+    }
+
+    pub fn from_assembly(assembly: Assembly) -> Image {
+        Image {
+            entry: 0,
+            symbols: assembly.labels,
+            program: assembly.program,
+        }
+    }
+
+    pub fn vm(&self) -> VM {
+        VM::restore(Snapshot {
+            memory: Memory::dense(self.program.clone()),
+            wide: BTreeMap::new(),
+            arithmetic: Arithmetic::Checked,
+            input: VecDeque::new(),
+            pc: self.entry,
+            relative_base: 0,
+            count: 0,
+        })
+    }
+
+    pub fn save<W: Write>(&self, mut write: W) -> Result<()> {
+        let mut buf = MAGIC.to_vec();
+        write_varint(&mut buf, VERSION);
+        write_varint(&mut buf, self.entry as i128);
+        write_varint(&mut buf, self.symbols.len() as i128);
+        for (name, &addr) in &self.symbols {
+            write_varint(&mut buf, name.len() as i128);
+            buf.extend(name.as_bytes());
+            write_varint(&mut buf, addr as i128);
+        }
+        write_varint(&mut buf, self.program.len() as i128);
+        for &word in &self.program {
+            write_varint(&mut buf, word as i128);
+        }
+        write.write_all(&buf)?;
+        Ok(())
+    }
+
+    pub fn load<R: BufRead>(mut read: R) -> Result<Image> {
+        let mut magic = [0; 4];
+        read.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            Err("not a program image")?
+        }
+        let version = read_field(&mut read, i128::MAX)?;
+        if version != VERSION {
+            Err(format!("unsupported program image version {}", version))?
+        }
+        let entry = read_field(&mut read, usize::MAX as i128)? as usize;
+        let mut symbols = BTreeMap::new();
+        for _ in 0..read_field(&mut read, usize::MAX as i128)? {
+            let mut name = vec![0; read_field(&mut read, u16::MAX as i128)? as usize];
+            read.read_exact(&mut name)?;
+            let addr = read_field(&mut read, usize::MAX as i128)? as usize;
+            symbols.insert(String::from_utf8(name)?, addr);
+        }
+        let len = read_field(&mut read, usize::MAX as i128)?;
+        let mut program = Vec::new();
+        for _ in 0..len {
+            let word = read_varint(&mut read)?.ok_or("truncated program image")?;
+            if word != word as i64 as i128 {
+                Err(format!("word {} out of range", word))?
+            }
+            program.push(word as i64);
+        }
+        Ok(Image {
+            entry,
+            symbols,
+            program,
+        })
+    }
+}
+
+fn read_field<R: BufRead>(read: &mut R, limit: i128) -> Result<i128> {
+    let value = read_varint(read)?.ok_or("truncated program image")?;
+    if value < 0 || value > limit {
+        Err(format!("invalid value {} in program image header", value))?
+    }
+    Ok(value)
+}
+
+#[test]
+fn test() {
+    use super::asm::assemble;
+
+    let assembly = assemble("start: in -> [x]\n out [x]\n hlt\n x: .data -1").unwrap();
+    let mut image = Image::from_assembly(assembly);
+    image.program.push(i64::MIN);
+    let mut saved = Vec::new();
+    image.save(&mut saved).unwrap();
+    assert_eq!(&saved[..6], b"ICPG\x02\x00");
+    assert_eq!(
+        saved.len(),
+        4 + 1 + 1 + 1 + (1 + 5 + 1) + (1 + 1 + 1) + 1 + 6 + 1 + 10
+    );
+    assert_eq!(Image::load(&saved[..]).unwrap(), image);
+
+    // Words are zig-zag encoded, so small negative numbers stay small.
+    let input = include_bytes!("../../input/05");
+    let program = super::get_program(&input[..]).unwrap();
+    let mut saved = Vec::new();
+    Image::new(program.clone()).save(&mut saved).unwrap();
+    assert!(saved.len() * 2 < input.len());
+    assert_eq!(super::get_program(&saved[..]).unwrap(), program);
+
+    let mut image = Image::new(vec![99, 104, 3, 99]);
+    image.entry = 1;
+    let mut output = Vec::new();
+    image.vm().resume(&[][..], &mut output).unwrap();
+    assert_eq!(output, vec![3]);
+    let mut saved = Vec::new();
+    image.save(&mut saved).unwrap();
+    assert!(super::get_program(&saved[..]).is_err());
+    saved[4] = 4;
+    let error = Image::load(&saved[..]).unwrap_err();
+    assert_eq!(error.to_string(), "unsupported program image version 2");
+}
@@ -0,0 +1,274 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use super::error::VmErrorKind;
+use super::memory::Memory;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    Halt,
+    Add { a: Param, b: Param, c: Param },
+    Mul { a: Param, b: Param, c: Param },
+    Input(Param),
+    Output(Param),
+    JumpIfTrue { cond: Param, next: Param },
+    JumpIfFalse { cond: Param, next: Param },
+    LessThan { a: Param, b: Param, c: Param },
+    Eq { a: Param, b: Param, c: Param },
+    AdjustRelativeBase(Param),
+}
+
+impl Instruction {
+    #[inline]
+    pub fn from_slice(slice: &[i64]) -> Result<Instruction, VmErrorKind> {
+        let word = |idx: usize| slice.get(idx).cloned().unwrap_or(0);
+        let command = u64::try_from(word(0)).map_err(|_| VmErrorKind::UnknownOpcode)?;
+        let op = command % 100;
+        let get_param = |idx: usize| {
+            let mode = (command / 10_u64.pow(idx as u32 + 2)) % 10;
+            let param = word(idx + 1);
+            let res = match mode {
+                0 => Param::Position(
+                    usize::try_from(param).map_err(|_| VmErrorKind::NegativeAddress(param))?,
+                ),
+                1 => Param::Immediate(param),
+                2 => Param::Relative(param),
+                _ => return Err(VmErrorKind::BadParameterMode(mode)),
+            };
+            Ok(res)
+        };
+
+        let res = match op {
+            99 => Instruction::Halt,
+            1 => Instruction::Add {
+                a: get_param(0)?,
+                b: get_param(1)?,
+                c: get_param(2)?.writable()?,
+            },
+            2 => Instruction::Mul {
+                a: get_param(0)?,
+                b: get_param(1)?,
+                c: get_param(2)?.writable()?,
+            },
+            3 => Instruction::Input(get_param(0)?.writable()?),
+            4 => Instruction::Output(get_param(0)?),
+            5 => Instruction::JumpIfTrue {
+                cond: get_param(0)?,
+                next: get_param(1)?,
+            },
+            6 => Instruction::JumpIfFalse {
+                cond: get_param(0)?,
+                next: get_param(1)?,
+            },
+            7 => Instruction::LessThan {
+                a: get_param(0)?,
+                b: get_param(1)?,
+                c: get_param(2)?.writable()?,
+            },
+            8 => Instruction::Eq {
+                a: get_param(0)?,
+                b: get_param(1)?,
+                c: get_param(2)?.writable()?,
+            },
+            9 => Instruction::AdjustRelativeBase(get_param(0)?),
+            _ => return Err(VmErrorKind::UnknownOpcode),
+        };
+        Ok(res)
+    }
+
+    #[inline]
+    pub fn pc_offset(&self) -> usize {
+        match self {
+            Instruction::Halt => 1,
+            Instruction::Add { .. } => 4,
+            Instruction::Mul { .. } => 4,
+            Instruction::Input(..) => 2,
+            Instruction::Output(..) => 2,
+            Instruction::JumpIfTrue { .. } => 3,
+            Instruction::JumpIfFalse { .. } => 3,
+            Instruction::LessThan { .. } => 4,
+            Instruction::Eq { .. } => 4,
+            Instruction::AdjustRelativeBase(..) => 2,
+        }
+    }
+
+    pub fn opcode(&self) -> i64 {
+        match self {
+            Instruction::Halt => 99,
+            Instruction::Add { .. } => 1,
+            Instruction::Mul { .. } => 2,
+            Instruction::Input(..) => 3,
+            Instruction::Output(..) => 4,
+            Instruction::JumpIfTrue { .. } => 5,
+            Instruction::JumpIfFalse { .. } => 6,
+            Instruction::LessThan { .. } => 7,
+            Instruction::Eq { .. } => 8,
+            Instruction::AdjustRelativeBase(..) => 9,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::Halt => "HLT",
+            Instruction::Add { .. } => "ADD",
+            Instruction::Mul { .. } => "MUL",
+            Instruction::Input(..) => "IN",
+            Instruction::Output(..) => "OUT",
+            Instruction::JumpIfTrue { .. } => "JT",
+            Instruction::JumpIfFalse { .. } => "JF",
+            Instruction::LessThan { .. } => "LT",
+            Instruction::Eq { .. } => "EQ",
+            Instruction::AdjustRelativeBase(..) => "ARB",
+        }
+    }
+
+    pub fn params(&self) -> Vec<Param> {
+        match *self {
+            Instruction::Halt => vec![],
+            Instruction::Add { a, b, c }
+            | Instruction::Mul { a, b, c }
+            | Instruction::LessThan { a, b, c }
+            | Instruction::Eq { a, b, c } => vec![a, b, c],
+            Instruction::Input(param)
+            | Instruction::Output(param)
+            | Instruction::AdjustRelativeBase(param) => vec![param],
+            Instruction::JumpIfTrue { cond, next } | Instruction::JumpIfFalse { cond, next } => {
+                vec![cond, next]
+            }
+        }
+    }
+
+    pub fn target(&self) -> Option<Param> {
+        match *self {
+            Instruction::Add { c, .. }
+            | Instruction::Mul { c, .. }
+            | Instruction::LessThan { c, .. }
+            | Instruction::Eq { c, .. } => Some(c),
+            Instruction::Input(param) => Some(param),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<i64> {
+        let params = self.params();
+        let mut command = self.opcode();
+        for (idx, param) in params.iter().enumerate() {
+            command += param.mode() * 10_i64.pow(idx as u32 + 2);
+        }
+        let mut words = vec![command];
+        words.extend(params.iter().map(Param::value));
+        words
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+        let mut params = self.params();
+        let target = self.target().and_then(|_| params.pop());
+        for (idx, param) in params.iter().enumerate() {
+            let separator = if idx == 0 { " " } else { ", " };
+            write!(f, "{}{}", separator, param)?;
+        }
+        if let Some(target) = target {
+            write!(f, " -> {}", target)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Param {
+    Position(usize),
+    Immediate(i64),
+    Relative(i64),
+}
+
+impl Param {
+    pub fn mode(&self) -> i64 {
+        match self {
+            Param::Position(_) => 0,
+            Param::Immediate(_) => 1,
+            Param::Relative(_) => 2,
+        }
+    }
+
+    pub fn value(&self) -> i64 {
+        match *self {
+            Param::Position(addr) => addr as i64,
+            Param::Immediate(x) | Param::Relative(x) => x,
+        }
+    }
+
+    fn writable(self) -> Result<Param, VmErrorKind> {
+        match self {
+            Param::Immediate(_) => Err(VmErrorKind::ImmediateWrite),
+            param => Ok(param),
+        }
+    }
+
+    #[inline]
+    pub fn address(&self, relative_base: i64) -> Result<usize, VmErrorKind> {
+        match self {
+            Param::Position(addr) => Ok(*addr),
+            Param::Immediate(_) => Err(VmErrorKind::ImmediateWrite),
+            Param::Relative(offset) => {
+                let addr = relative_base
+                    .checked_add(*offset)
+                    .ok_or(VmErrorKind::Overflow)?;
+                usize::try_from(addr).map_err(|_| VmErrorKind::NegativeAddress(addr))
+            }
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, memory: &Memory, relative_base: i64) -> Result<i64, VmErrorKind> {
+        match self {
+            Param::Immediate(x) => Ok(*x),
+            param => Ok(memory[param.address(relative_base)?]),
+        }
+    }
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Param::Position(addr) => write!(f, "[{}]", addr),
+            Param::Immediate(x) => write!(f, "#{}", x),
+            Param::Relative(offset) if *offset < 0 => write!(f, "[rb{}]", offset),
+            Param::Relative(offset) => write!(f, "[rb+{}]", offset),
+        }
+    }
+}
+
+#[test]
+fn test() {
+    assert_eq!(
+        Instruction::from_slice(&[1002, 4, 3, 4]),
+        Ok(Instruction::Mul {
+            a: Param::Position(4),
+            b: Param::Immediate(3),
+            c: Param::Position(4)
+        })
+    );
+    assert_eq!(
+        Instruction::from_slice(&[21201, -3, 7, 2]),
+        Ok(Instruction::Add {
+            a: Param::Relative(-3),
+            b: Param::Immediate(7),
+            c: Param::Relative(2)
+        })
+    );
+    assert_eq!(
+        Instruction::from_slice(&[11101, 1, 2, 3]),
+        Err(VmErrorKind::ImmediateWrite)
+    );
+    assert_eq!(
+        Instruction::from_slice(&[304, 1]),
+        Err(VmErrorKind::BadParameterMode(3))
+    );
+    assert_eq!(
+        Instruction::from_slice(&[42]),
+        Err(VmErrorKind::UnknownOpcode)
+    );
+}
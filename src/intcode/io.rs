@@ -0,0 +1,161 @@
+use std::collections::VecDeque;
+use std::io::prelude::*;
+use std::sync::mpsc::{Receiver, Sender};
+
+use super::error::VmErrorKind;
+
+pub trait IntcodeInput {
+    fn read(&mut self) -> Result<Option<i64>, VmErrorKind>;
+}
+
+pub trait IntcodeOutput {
+    fn write(&mut self, value: i64) -> Result<(), VmErrorKind>;
+}
+
+impl<T: IntcodeInput + ?Sized> IntcodeInput for &mut T {
+    fn read(&mut self) -> Result<Option<i64>, VmErrorKind> {
+        (**self).read()
+    }
+}
+
+impl<T: IntcodeOutput + ?Sized> IntcodeOutput for &mut T {
+    fn write(&mut self, value: i64) -> Result<(), VmErrorKind> {
+        (**self).write(value)
+    }
+}
+
+impl IntcodeInput for &[i64] {
+    fn read(&mut self) -> Result<Option<i64>, VmErrorKind> {
+        Ok(self.split_first().map(|(first, rest)| {
+            *self = rest;
+            *first
+        }))
+    }
+}
+
+impl IntcodeInput for VecDeque<i64> {
+    fn read(&mut self) -> Result<Option<i64>, VmErrorKind> {
+        Ok(self.pop_front())
+    }
+}
+
+impl IntcodeOutput for VecDeque<i64> {
+    fn write(&mut self, value: i64) -> Result<(), VmErrorKind> {
+        self.push_back(value);
+        Ok(())
+    }
+}
+
+impl IntcodeOutput for Vec<i64> {
+    fn write(&mut self, value: i64) -> Result<(), VmErrorKind> {
+        self.push(value);
+        Ok(())
+    }
+}
+
+impl IntcodeInput for Receiver<i64> {
+    fn read(&mut self) -> Result<Option<i64>, VmErrorKind> {
+        Ok(self.recv().ok())
+    }
+}
+
+impl IntcodeOutput for Sender<i64> {
+    fn write(&mut self, value: i64) -> Result<(), VmErrorKind> {
+        // A hung-up receiver has stopped listening; the value has nowhere to go.
+        let _ = self.send(value);
+        Ok(())
+    }
+}
+
+pub struct IterInput<I>(pub I);
+
+impl<I: Iterator<Item = i64>> IntcodeInput for IterInput<I> {
+    fn read(&mut self) -> Result<Option<i64>, VmErrorKind> {
+        Ok(self.0.next())
+    }
+}
+
+pub struct FnInput<F>(pub F);
+
+impl<F: FnMut() -> Option<i64>> IntcodeInput for FnInput<F> {
+    fn read(&mut self) -> Result<Option<i64>, VmErrorKind> {
+        Ok((self.0)())
+    }
+}
+
+pub struct FnOutput<F>(pub F);
+
+impl<F: FnMut(i64)> IntcodeOutput for FnOutput<F> {
+    fn write(&mut self, value: i64) -> Result<(), VmErrorKind> {
+        (self.0)(value);
+        Ok(())
+    }
+}
+
+pub struct LineInput<R>(pub R);
+
+impl<R: BufRead> IntcodeInput for LineInput<R> {
+    fn read(&mut self) -> Result<Option<i64>, VmErrorKind> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = self.0.read_line(&mut line);
+            if read.map_err(|err| VmErrorKind::Io(err.to_string()))? == 0 {
+                return Ok(None);
+            }
+            let line = line.trim();
+            if !line.is_empty() {
+                let value = line.parse();
+                return value
+                    .map(Some)
+                    .map_err(|_| VmErrorKind::BadInput(line.to_string()));
+            }
+        }
+    }
+}
+
+pub struct LineOutput<W>(pub W);
+
+impl<W: Write> IntcodeOutput for LineOutput<W> {
+    fn write(&mut self, value: i64) -> Result<(), VmErrorKind> {
+        writeln!(self.0, "{}", value).map_err(|err| VmErrorKind::Io(err.to_string()))
+    }
+}
+
+#[test]
+fn test() {
+    let mut input: &[i64] = &[1, 2];
+    assert_eq!(
+        (input.read(), input.read(), input.read()),
+        (Ok(Some(1)), Ok(Some(2)), Ok(None))
+    );
+
+    let mut input = LineInput(&b"3\n\n-4\n1x\n5\n"[..]);
+    assert_eq!(
+        (input.read(), input.read(), input.read(), input.read()),
+        (
+            Ok(Some(3)),
+            Ok(Some(-4)),
+            Err(VmErrorKind::BadInput("1x".to_string())),
+            Ok(Some(5))
+        )
+    );
+    assert_eq!(input.read(), Ok(None));
+
+    let mut input = IterInput(5..);
+    assert_eq!((input.read(), input.read()), (Ok(Some(5)), Ok(Some(6))));
+
+    let (mut sender, mut receiver) = std::sync::mpsc::channel();
+    sender.write(7).unwrap();
+    drop(sender);
+    assert_eq!((receiver.read(), receiver.read()), (Ok(Some(7)), Ok(None)));
+
+    let mut output = LineOutput(Vec::new());
+    output.write(8).unwrap();
+    output.write(-9).unwrap();
+    assert_eq!(output.0, b"8\n-9\n");
+
+    let mut output = LineOutput(&mut [0u8; 3][..]);
+    assert!(output.write(10).is_ok());
+    assert!(matches!(output.write(11), Err(VmErrorKind::Io(_))));
+}
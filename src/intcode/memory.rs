@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+use std::ops::Index;
+
+const PAGE_SIZE: usize = 1024;
+
+// Dense memory switches to pages rather than grow past this many words.
+const DENSE_LIMIT: usize = 1 << 24;
+
+type Page = Box<[i64; PAGE_SIZE]>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Memory {
+    Dense(Vec<i64>),
+    Paged(BTreeMap<usize, Page>),
+}
+
+impl Memory {
+    pub fn dense(program: Vec<i64>) -> Memory {
+        Memory::Dense(program)
+    }
+
+    pub fn paged(program: &[i64]) -> Memory {
+        let mut memory = Memory::Paged(BTreeMap::new());
+        for (addr, value) in program.iter().cloned().enumerate() {
+            memory.set(addr, value);
+        }
+        memory
+    }
+
+    #[inline]
+    pub fn get(&self, addr: usize) -> i64 {
+        self[addr]
+    }
+
+    #[inline]
+    pub fn fetch(&self, addr: usize) -> [i64; 4] {
+        [self[addr], self[addr + 1], self[addr + 2], self[addr + 3]]
+    }
+
+    #[inline]
+    pub fn set(&mut self, addr: usize, value: i64) {
+        match self {
+            Memory::Dense(words) if addr >= words.len() && addr >= DENSE_LIMIT => {
+                if value != 0 {
+                    *self = Memory::paged(words);
+                    self.set(addr, value);
+                }
+            }
+            Memory::Dense(words) => {
+                if addr >= words.len() {
+                    if value == 0 {
+                        return;
+                    }
+                    words.resize(addr + 1, 0);
+                }
+                words[addr] = value;
+            }
+            Memory::Paged(pages) => {
+                let (page, offset) = (addr / PAGE_SIZE, addr % PAGE_SIZE);
+                if let Some(page) = pages.get_mut(&page) {
+                    page[offset] = value;
+                } else if value != 0 {
+                    let mut words = Box::new([0; PAGE_SIZE]);
+                    words[offset] = value;
+                    pages.insert(page, words);
+                }
+            }
+        }
+    }
+
+    // Backs an address with storage even while it holds zero, which `set` leaves unbacked.
+    pub fn touch(&mut self, addr: usize) {
+        match self {
+            Memory::Dense(words) if addr >= words.len() && addr >= DENSE_LIMIT => {
+                *self = Memory::paged(words);
+                self.touch(addr);
+            }
+            Memory::Dense(words) => {
+                if addr >= words.len() {
+                    words.resize(addr + 1, 0);
+                }
+            }
+            Memory::Paged(pages) => {
+                let page = addr / PAGE_SIZE;
+                pages
+                    .entry(page)
+                    .or_insert_with(|| Box::new([0; PAGE_SIZE]));
+            }
+        }
+    }
+
+    pub fn segments(&self) -> Vec<(usize, &[i64])> {
+        match self {
+            Memory::Dense(words) => vec![(0, &words[..])],
+            Memory::Paged(pages) => pages
+                .iter()
+                .map(|(page, words)| (page * PAGE_SIZE, &words[..]))
+                .collect(),
+        }
+    }
+
+    pub fn extent(&self) -> usize {
+        match self {
+            Memory::Dense(words) => words.len(),
+            Memory::Paged(pages) => pages
+                .keys()
+                .next_back()
+                .map_or(0, |page| (page + 1) * PAGE_SIZE),
+        }
+    }
+}
+
+impl Index<usize> for Memory {
+    type Output = i64;
+
+    #[inline]
+    fn index(&self, addr: usize) -> &i64 {
+        match self {
+            Memory::Dense(words) => words.get(addr).unwrap_or(&0),
+            Memory::Paged(pages) => pages
+                .get(&(addr / PAGE_SIZE))
+                .map_or(&0, |page| &page[addr % PAGE_SIZE]),
+        }
+    }
+}
+
+impl From<Vec<i64>> for Memory {
+    fn from(program: Vec<i64>) -> Memory {
+        Memory::dense(program)
+    }
+}
+
+#[test]
+fn test() {
+    let mut memory = Memory::dense(vec![1, 2, 3]);
+    assert_eq!(memory[100], 0);
+    memory.set(100, 7);
+    assert_eq!((memory[100], memory.extent()), (7, 101));
+    memory.set(1 << 45, 8);
+    assert_eq!((memory[2], memory[100], memory[1 << 45]), (3, 7, 8));
+    assert!(matches!(memory, Memory::Paged(_)));
+
+    let mut memory = Memory::paged(&[1, 2, 3]);
+    memory.set(1_000_000_000, 7);
+    assert_eq!((memory[2], memory[1_000_000_000], memory[999]), (3, 7, 0));
+    if let Memory::Paged(pages) = &memory {
+        assert_eq!(pages.len(), 2);
+    }
+}
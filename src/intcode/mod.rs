@@ -0,0 +1,88 @@
+use std::error::Error;
+use std::io::prelude::*;
+
+pub mod asm;
+pub mod cfg;
+pub mod compile;
+pub mod coverage;
+pub mod decompile;
+pub mod disasm;
+mod error;
+pub mod image;
+mod instruction;
+mod io;
+mod memory;
+pub mod optimize;
+pub mod profile;
+pub mod selfmod;
+mod snapshot;
+pub mod symbolic;
+pub mod trace;
+mod varint;
+mod vm;
+
+pub use error::{VmError, VmErrorKind};
+pub use instruction::{Instruction, Param};
+pub use io::{FnInput, FnOutput, IntcodeInput, IntcodeOutput, IterInput, LineInput, LineOutput};
+pub use memory::Memory;
+pub use snapshot::Snapshot;
+pub use vm::{
+    execute, Access, Arithmetic, DecodeCache, Execution, Limit, Status, Suspension, Watch, VM,
+};
+
+pub type Result<T> = std::result::Result<T, Box<dyn Error + 'static>>;
+
+pub const RUNS_HELP: &str = "\
+Each inputs argument is a comma-separated list fed to a fresh run; the
+results of all runs are combined.";
+
+pub fn get_program<R: BufRead>(mut read: R) -> Result<Vec<i64>> {
+    if read.fill_buf()?.starts_with(image::MAGIC) {
+        let image = image::Image::load(read)?;
+        if image.entry != 0 {
+            Err(format!("program image starts at {}", image.entry))?
+        }
+        return Ok(image.program);
+    }
+
+    let mut inputs = Vec::new();
+    for value in read.split(b',') {
+        let value = value?;
+        let value = std::str::from_utf8(&value)?;
+        let value = value.trim();
+        if value.is_empty() {
+            continue;
+        }
+        let number = value.parse::<i64>()?;
+        inputs.push(number)
+    }
+
+    Ok(inputs)
+}
+
+// Each argument is one run's comma-separated inputs; no arguments makes one run without input.
+pub fn parse_runs<S: AsRef<str>>(args: &[S]) -> Result<Vec<Vec<i64>>> {
+    let mut runs = Vec::new();
+    for arg in args {
+        runs.push(get_program(arg.as_ref().as_bytes())?);
+    }
+    if runs.is_empty() {
+        runs.push(Vec::new());
+    }
+    Ok(runs)
+}
+
+pub fn run_many<F: FnMut(&mut VM)>(
+    program: &[i64],
+    runs: &[Vec<i64>],
+    mut configure: F,
+) -> Result<Vec<VM>> {
+    let mut vms = Vec::new();
+    for inputs in runs {
+        let mut vm = VM::new(program.to_vec());
+        configure(&mut vm);
+        vm.resume(&inputs[..], Vec::new())?;
+        vms.push(vm);
+    }
+    Ok(vms)
+}
@@ -0,0 +1,352 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::cfg::Cfg;
+use super::instruction::{Instruction, Param};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Optimized {
+    pub program: Vec<i64>,
+    pub folded: usize,
+    pub jumps: usize,
+    pub removed: usize,
+}
+
+// The rewritten form of a reachable instruction, or `None` if it does nothing at all.
+fn rewrite(inst: Instruction) -> Option<Instruction> {
+    let constant = |x: Option<i64>, c: Param| {
+        let a = Param::Immediate(x?);
+        Some(Instruction::Add {
+            a,
+            b: Param::Immediate(0),
+            c,
+        })
+    };
+    let folded = match inst {
+        Instruction::Add {
+            a: Param::Immediate(a),
+            b: Param::Immediate(b),
+            c,
+        } => constant(a.checked_add(b), c),
+        Instruction::Mul {
+            a: Param::Immediate(a),
+            b: Param::Immediate(b),
+            c,
+        } => constant(a.checked_mul(b), c),
+        _ => None,
+    };
+    if folded.is_some() {
+        return folded;
+    }
+    match inst {
+        Instruction::JumpIfTrue {
+            cond: Param::Immediate(cond),
+            next,
+        } if cond != 0 => Some(Instruction::JumpIfTrue {
+            cond: Param::Immediate(1),
+            next,
+        }),
+        Instruction::JumpIfFalse {
+            cond: Param::Immediate(0),
+            next,
+        } => Some(Instruction::JumpIfTrue {
+            cond: Param::Immediate(1),
+            next,
+        }),
+        Instruction::JumpIfTrue {
+            cond: Param::Immediate(_),
+            ..
+        }
+        | Instruction::JumpIfFalse {
+            cond: Param::Immediate(_),
+            ..
+        } => None,
+        _ => Some(inst),
+    }
+}
+
+// A jump on a cell set to a constant earlier in the block jumps on the constant instead.
+fn propagate(inst: Instruction, known: &BTreeMap<usize, i64>) -> Instruction {
+    let known = |cond: Param| match cond {
+        Param::Position(addr) => known.get(&addr).map_or(cond, |&x| Param::Immediate(x)),
+        _ => cond,
+    };
+    match inst {
+        Instruction::JumpIfTrue { cond, next } => Instruction::JumpIfTrue {
+            cond: known(cond),
+            next,
+        },
+        Instruction::JumpIfFalse { cond, next } => Instruction::JumpIfFalse {
+            cond: known(cond),
+            next,
+        },
+        _ => inst,
+    }
+}
+
+fn track(known: &mut BTreeMap<usize, i64>, inst: Instruction) {
+    match inst {
+        Instruction::Add {
+            a: Param::Immediate(a),
+            b: Param::Immediate(0),
+            c: Param::Position(addr),
+        } => {
+            known.insert(addr, a);
+        }
+        _ => match inst.target() {
+            Some(Param::Position(addr)) => {
+                known.remove(&addr);
+            }
+            Some(_) => known.clear(),
+            None => {}
+        },
+    }
+}
+
+fn unconditional(inst: &Instruction) -> Option<Param> {
+    match *inst {
+        Instruction::JumpIfTrue {
+            cond: Param::Immediate(1),
+            next,
+        } => Some(next),
+        _ => None,
+    }
+}
+
+pub fn optimize(program: &[i64]) -> Optimized {
+    optimize_from(program, &[0])
+}
+
+// Instructions the program addresses as data are left alone.
+pub fn optimize_from(program: &[i64], entries: &[usize]) -> Optimized {
+    let cfg = Cfg::build_from(program, entries);
+    let instructions: Vec<(usize, Instruction)> = cfg
+        .blocks
+        .values()
+        .flat_map(|block| block.instructions.iter().copied())
+        .collect();
+
+    let mut addressed = BTreeSet::new();
+    let mut relative = false;
+    for (_, inst) in &instructions {
+        for param in inst.params() {
+            match param {
+                Param::Position(addr) => {
+                    addressed.insert(addr);
+                }
+                Param::Relative(_) => relative = true,
+                Param::Immediate(_) => {}
+            }
+        }
+    }
+
+    let mut optimized = Optimized {
+        program: program.to_vec(),
+        folded: 0,
+        jumps: 0,
+        removed: 0,
+    };
+    // Otherwise any word may be reached some way the CFG doesn't show, so none can change.
+    let closed = !relative
+        && cfg.undecodable.is_empty()
+        && cfg.indirect_jumps().is_empty()
+        && addressed.iter().all(|&addr| !in_code(&instructions, addr));
+    if !closed {
+        return optimized;
+    }
+
+    let mut code = Vec::new();
+    for block in cfg.blocks.values() {
+        // Only the block's first instruction can be jumped to, so these hold up to the end.
+        let mut known = BTreeMap::new();
+        for &(addr, inst) in &block.instructions {
+            let words = addr..addr + inst.pc_offset();
+            if addressed.range(words.clone()).next().is_some() || cfg.modified.contains(&addr) {
+                known.clear();
+                code.push((addr, Some(inst)));
+                continue;
+            }
+            let rewritten = rewrite(propagate(inst, &known));
+            let encoded = rewritten.unwrap_or(Instruction::JumpIfTrue {
+                cond: Param::Immediate(0),
+                next: Param::Immediate(0),
+            });
+            if encoded != inst {
+                match inst {
+                    Instruction::Add { .. } | Instruction::Mul { .. } => optimized.folded += 1,
+                    _ => optimized.jumps += 1,
+                }
+                optimized.program[words].copy_from_slice(&encoded.encode());
+            }
+            track(&mut known, encoded);
+            code.push((addr, rewritten));
+        }
+    }
+
+    compact(&mut optimized, &code, &addressed);
+    optimized
+}
+
+fn in_code(instructions: &[(usize, Instruction)], addr: usize) -> bool {
+    instructions
+        .iter()
+        .any(|(start, inst)| (*start..start + inst.pc_offset()).contains(&addr))
+}
+
+// Keeps the words of live instructions and the ones they address, in order, and shifts every
+// address down past the words dropped below it.
+fn compact(
+    optimized: &mut Optimized,
+    code: &[(usize, Option<Instruction>)],
+    addressed: &BTreeSet<usize>,
+) {
+    let len = optimized.program.len();
+    let mut keep = vec![false; len];
+    for &addr in addressed.range(..len) {
+        keep[addr] = true;
+    }
+    let mut live: Vec<(usize, Instruction)> = code
+        .iter()
+        .filter_map(|&(addr, inst)| Some((addr, inst?)))
+        .collect();
+    for &(addr, inst) in &live {
+        keep[addr..addr + inst.pc_offset()].fill(true);
+    }
+    // A jump over nothing but dropped words is a no-op too, once the program is compact.
+    loop {
+        let skip = live.iter().position(|&(addr, inst)| {
+            let end = addr + inst.pc_offset();
+            let target = match unconditional(&inst) {
+                Some(Param::Immediate(target)) if (end..=len).contains(&(target as usize)) => {
+                    target as usize
+                }
+                _ => return false,
+            };
+            (end..target).all(|word| !keep[word])
+        });
+        let (addr, inst) = match skip {
+            Some(idx) => live.remove(idx),
+            None => break,
+        };
+        keep[addr..addr + inst.pc_offset()].fill(false);
+    }
+
+    let mut shift = Vec::with_capacity(len + 1);
+    let mut removed = 0;
+    for &kept in &keep {
+        shift.push(removed);
+        if !kept {
+            removed += 1;
+        }
+    }
+    shift.push(removed);
+    let relocate = |addr: usize| addr - shift[addr.min(len)];
+
+    let mut program = Vec::with_capacity(len - removed);
+    let mut live = live.into_iter().peekable();
+    let mut addr = 0;
+    while addr < len {
+        if !keep[addr] {
+            addr += 1;
+            continue;
+        }
+        match live.peek() {
+            Some(&(start, inst)) if start == addr => {
+                let params = inst.params().into_iter().enumerate();
+                let mut words = inst.encode();
+                for (idx, param) in params {
+                    words[idx + 1] = match param {
+                        Param::Position(target) => relocate(target) as i64,
+                        Param::Immediate(target) if is_target(&inst, idx) && target >= 0 => {
+                            relocate(target as usize) as i64
+                        }
+                        param => param.value(),
+                    };
+                }
+                addr += words.len();
+                program.extend(words);
+                live.next();
+            }
+            _ => {
+                program.push(optimized.program[addr]);
+                addr += 1;
+            }
+        }
+    }
+    optimized.removed = removed;
+    optimized.program = program;
+}
+
+fn is_target(inst: &Instruction, idx: usize) -> bool {
+    matches!(
+        inst,
+        Instruction::JumpIfTrue { .. } | Instruction::JumpIfFalse { .. }
+    ) && idx == 1
+}
+
+#[test]
+fn test() {
+    use super::asm::assemble;
+    use super::vm::VM;
+
+    let run = |program: &[i64], inputs: &[i64]| {
+        let mut output = Vec::new();
+        VM::new(program.to_vec())
+            .resume(inputs, &mut output)
+            .unwrap();
+        output
+    };
+
+    let source = "
+                in -> [n]
+                add #2, #3 -> [step]
+                mul #0, #7 -> [skip]
+                jt [skip], #loop
+                jf #0, #loop
+                out #-1
+        loop:   mul [n], [step] -> [n]
+                jt #0, #nowhere
+                lt [n], #1000 -> [more]
+                jt [more], #loop
+                out [n]
+                hlt
+        nowhere: out #-2
+                hlt
+        n:      .data 0
+        step:   .data 0
+        more:   .data 0
+        skip:   .data 0
+    ";
+    let program = assemble(source).unwrap().program;
+    let optimized = optimize(&program);
+    assert_eq!(
+        (optimized.folded, optimized.jumps, optimized.removed),
+        (2, 3, 3 + 2 + 3 + 3 + 3)
+    );
+    assert_eq!(run(&optimized.program, &[3]), run(&program, &[3]));
+
+    // The jump at 0009 goes through memory to 0004, where an add overlapping the no-op at
+    // 0006 runs on into the halt in its last word.
+    let program = [4, 12003, 7, 3, 201, 14, 2106, 6, 99, 6, 11208];
+    assert_eq!(optimize(&program).program, program);
+    assert_eq!(run(&program, &[]), vec![0]);
+
+    // input/05 patches the opcode at 0006 into an add that falls through to 0010 for
+    // input 1, and into a jump to 0238 for input 5, so it is left as it is.
+    let input = include_bytes!("../../input/05");
+    let program = super::get_program(&input[..]).unwrap();
+    let optimized = optimize_from(&program, &[0, 10, 238]);
+    assert_eq!(optimized.program, program);
+    for &id in &[1, 5] {
+        assert_eq!(run(&optimized.program, &[id]), run(&program, &[id]));
+    }
+
+    let input = include_bytes!("../../input/07");
+    let program = super::get_program(&input[..]).unwrap();
+    let mut entries = vec![0];
+    entries.extend(program[10..20].iter().map(|&addr| addr as usize));
+    let optimized = optimize_from(&program, &entries);
+    for phase in 0..10 {
+        let inputs = [phase, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+        assert_eq!(run(&optimized.program, &inputs), run(&program, &inputs));
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use super::disasm::{decode_at, disassemble};
+use super::instruction::Instruction;
+
+const HOT_SPOTS: usize = 20;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Branch {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profile {
+    pub total: u64,
+    pub per_pc: BTreeMap<usize, u64>,
+    pub per_opcode: BTreeMap<i64, u64>,
+    pub branches: BTreeMap<usize, Branch>,
+}
+
+impl Profile {
+    pub fn new() -> Profile {
+        Profile::default()
+    }
+
+    pub fn record(&mut self, pc: usize, inst: &Instruction, taken: Option<bool>) {
+        self.total += 1;
+        *self.per_pc.entry(pc).or_insert(0) += 1;
+        *self.per_opcode.entry(inst.opcode()).or_insert(0) += 1;
+        if let Some(taken) = taken {
+            let branch = self.branches.entry(pc).or_default();
+            if taken {
+                branch.taken += 1;
+            } else {
+                branch.not_taken += 1;
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &Profile) {
+        self.total += other.total;
+        for (&pc, &count) in &other.per_pc {
+            *self.per_pc.entry(pc).or_insert(0) += count;
+        }
+        for (&opcode, &count) in &other.per_opcode {
+            *self.per_opcode.entry(opcode).or_insert(0) += count;
+        }
+        for (&pc, branch) in &other.branches {
+            let merged = self.branches.entry(pc).or_default();
+            merged.taken += branch.taken;
+            merged.not_taken += branch.not_taken;
+        }
+    }
+
+    pub fn hot_spots(&self) -> Vec<(usize, u64)> {
+        let mut hot_spots: Vec<_> = self.per_pc.iter().map(|(&pc, &n)| (pc, n)).collect();
+        hot_spots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hot_spots
+    }
+
+    pub fn report(&self, program: &[i64]) -> String {
+        let mut result = String::new();
+        let share = |count: u64| 100.0 * count as f64 / self.total.max(1) as f64;
+        writeln!(result, "total {} instructions", self.total).unwrap();
+
+        writeln!(result, "\nby opcode").unwrap();
+        let mut opcodes: Vec<_> = self.per_opcode.iter().collect();
+        opcodes.sort_by(|a, b| b.1.cmp(a.1));
+        for (&opcode, &count) in opcodes {
+            let name = mnemonic(opcode);
+            writeln!(result, "{:>5} {:>12} {:>6.2}%", name, count, share(count)).unwrap();
+        }
+
+        writeln!(result, "\nhot spots").unwrap();
+        for (pc, count) in self.hot_spots().into_iter().take(HOT_SPOTS) {
+            let text = decode_at(program, pc).map_or("?".to_string(), |inst| inst.to_string());
+            writeln!(
+                result,
+                "{:04}: {:>12} {:>6.2}%  {}",
+                pc,
+                count,
+                share(count),
+                text
+            )
+            .unwrap();
+        }
+
+        if !self.branches.is_empty() {
+            writeln!(result, "\nbranches").unwrap();
+        }
+        for (pc, branch) in &self.branches {
+            let (taken, not_taken) = (branch.taken, branch.not_taken);
+            writeln!(result, "{:04}: taken {} not taken {}", pc, taken, not_taken).unwrap();
+        }
+        result
+    }
+
+    // Code the program rewrote at runtime disassembles as data, but its counts still show.
+    pub fn annotate(&self, program: &[i64]) -> String {
+        let mut result = String::new();
+        for line in disassemble(program) {
+            match self.per_pc.get(&line.addr) {
+                Some(count) => write!(result, "{:>10}  {}", count, line).unwrap(),
+                None => write!(result, "{:>10}  {}", "", line).unwrap(),
+            }
+            if let Some(branch) = self.branches.get(&line.addr) {
+                let (taken, not_taken) = (branch.taken, branch.not_taken);
+                write!(result, "  ; taken {} not taken {}", taken, not_taken).unwrap();
+            }
+            result.push('\n');
+        }
+        result
+    }
+}
+
+fn mnemonic(opcode: i64) -> String {
+    let words = [opcode, 0, 0, 0];
+    match Instruction::from_slice(&words) {
+        Ok(inst) => inst.mnemonic().to_string(),
+        Err(_) => opcode.to_string(),
+    }
+}
+
+#[test]
+fn test() {
+    use super::vm::VM;
+
+    // Counts down from 3 and halts.
+    let program = vec![1101, 3, 0, 13, 1001, 13, -1, 13, 1005, 13, 4, 99, 0, 0];
+    let mut vm = VM::new(program.clone());
+    vm.set_profile(true);
+    vm.resume(&[][..], Vec::new()).unwrap();
+    let profile = vm.take_profile().unwrap();
+    assert_eq!(profile.total, 7);
+    assert_eq!(profile.total, vm.count());
+    assert_eq!(profile.hot_spots()[..2], [(4, 3), (8, 3)]);
+    assert_eq!(
+        profile.branches[&8],
+        Branch {
+            taken: 2,
+            not_taken: 1
+        }
+    );
+
+    let mut merged = profile.clone();
+    merged.merge(&profile);
+    assert_eq!((merged.total, merged.per_opcode[&1]), (14, 8));
+    assert_eq!(
+        profile.annotate(&program).lines().collect::<Vec<_>>()[..4],
+        [
+            "         1  0000: ADD #3, #0 -> [13]",
+            "         3  0004: ADD [13], #-1 -> [13]",
+            "         3  0008: JT [13], #4  ; taken 2 not taken 1",
+            "            0011: HLT",
+        ]
+    );
+    assert!(profile
+        .report(&program)
+        .contains("0004:            3  42.86%  ADD [13], #-1 -> [13]"));
+}
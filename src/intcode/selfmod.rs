@@ -0,0 +1,183 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use super::instruction::Instruction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Order {
+    Before,
+    After,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Site {
+    pub writer: usize,
+    pub addr: usize,
+    pub instruction: usize,
+    pub order: Order,
+}
+
+impl Site {
+    fn word(&self) -> String {
+        match self.addr - self.instruction {
+            0 => "opcode".to_string(),
+            offset => format!("param {}", offset),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SelfModification {
+    // The start of the last instruction each address executed as part of.
+    executed: BTreeMap<usize, usize>,
+    // Addresses written but not executed since, with the pc that wrote them.
+    pending: BTreeMap<usize, usize>,
+    sites: BTreeMap<Site, u64>,
+}
+
+impl SelfModification {
+    pub fn new() -> SelfModification {
+        SelfModification::default()
+    }
+
+    // An instruction's own words count as executed before its write lands.
+    pub fn record(&mut self, pc: usize, inst: &Instruction, write: Option<usize>) {
+        for addr in pc..pc + inst.pc_offset() {
+            self.executed.insert(addr, pc);
+            if let Some(writer) = self.pending.remove(&addr) {
+                self.hit(writer, addr, pc, Order::Before);
+            }
+        }
+        if let Some(addr) = write {
+            match self.executed.get(&addr).copied() {
+                Some(instruction) => self.hit(pc, addr, instruction, Order::After),
+                None => {
+                    self.pending.insert(addr, pc);
+                }
+            }
+        }
+    }
+
+    fn hit(&mut self, writer: usize, addr: usize, instruction: usize, order: Order) {
+        let site = Site {
+            writer,
+            addr,
+            instruction,
+            order,
+        };
+        *self.sites.entry(site).or_insert(0) += 1;
+    }
+
+    pub fn merge(&mut self, other: &SelfModification) {
+        for (&site, &count) in &other.sites {
+            *self.sites.entry(site).or_insert(0) += count;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sites.is_empty()
+    }
+
+    pub fn sites(&self) -> Vec<(Site, u64)> {
+        self.sites
+            .iter()
+            .map(|(&site, &count)| (site, count))
+            .collect()
+    }
+
+    pub fn report(&self) -> String {
+        let mut result = String::new();
+        if self.is_empty() {
+            result.push_str("no self-modifying writes\n");
+            return result;
+        }
+        writeln!(result, "{} self-modifying sites", self.sites.len()).unwrap();
+        for (site, count) in self.sites() {
+            let order = match site.order {
+                Order::Before => "before",
+                Order::After => "after",
+            };
+            writeln!(
+                result,
+                "{:04}: writes [{}], {} of {:04}, {} it runs ({}x)",
+                site.writer,
+                site.addr,
+                site.word(),
+                site.instruction,
+                order,
+                count
+            )
+            .unwrap();
+        }
+        result
+    }
+}
+
+#[test]
+fn test() {
+    use super::vm::VM;
+
+    // input/05 completes the opcode at 0006 by adding its first input to it.
+    let input = include_bytes!("../../input/05");
+    let program = super::get_program(&input[..]).unwrap();
+    let mut vm = VM::new(program);
+    vm.set_self_modification(true);
+    vm.resume(&[1][..], Vec::new()).unwrap();
+    let found = vm.take_self_modification().unwrap();
+    assert_eq!(
+        found.sites()[0],
+        (
+            Site {
+                writer: 2,
+                addr: 6,
+                instruction: 6,
+                order: Order::Before
+            },
+            1
+        )
+    );
+    assert!(found
+        .report()
+        .contains("0002: writes [6], opcode of 0006, before it runs (1x)"));
+
+    // Loops by counting down the immediate condition of the jump at 0004.
+    let mut vm = VM::new(vec![101, -1, 5, 5, 1105, 2, 0, 99]);
+    vm.set_self_modification(true);
+    vm.resume(&[][..], Vec::new()).unwrap();
+    let site = |order| Site {
+        writer: 0,
+        addr: 5,
+        instruction: 4,
+        order,
+    };
+    assert_eq!(
+        vm.self_modification().unwrap().sites(),
+        vec![(site(Order::Before), 1), (site(Order::After), 1)]
+    );
+
+    let mut vm = VM::new(vec![1101, 1, 2, 5, 99, 0]);
+    vm.set_self_modification(true);
+    vm.resume(&[][..], Vec::new()).unwrap();
+    assert!(vm.self_modification().unwrap().is_empty());
+
+    // Code far out in paged memory writes the halt it then runs into.
+    let far = 1_000_000_000;
+    let mut vm = VM::with_memory(super::memory::Memory::paged(&[1105, 1, far as i64]));
+    for (offset, &word) in [1101, 0, 99, far as i64 + 4].iter().enumerate() {
+        vm.set_memory(far + offset, word);
+    }
+    vm.set_self_modification(true);
+    vm.resume(&[][..], Vec::new()).unwrap();
+    assert_eq!(
+        vm.self_modification().unwrap().sites(),
+        vec![(
+            Site {
+                writer: far,
+                addr: far + 4,
+                instruction: far + 4,
+                order: Order::Before
+            },
+            1
+        )]
+    );
+}
@@ -0,0 +1,181 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io::prelude::*;
+
+use super::memory::Memory;
+use super::vm::Arithmetic;
+use super::Result;
+
+const HEADER: &str = "intcode-snapshot 1";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub memory: Memory,
+    pub wide: BTreeMap<usize, i128>,
+    pub arithmetic: Arithmetic,
+    pub input: VecDeque<i64>,
+    pub pc: usize,
+    pub relative_base: i64,
+    pub count: u64,
+}
+
+impl Snapshot {
+    pub fn save<W: Write>(&self, mut write: W) -> Result<()> {
+        writeln!(write, "{}", HEADER)?;
+        writeln!(write, "pc {}", self.pc)?;
+        writeln!(write, "relative_base {}", self.relative_base)?;
+        writeln!(write, "count {}", self.count)?;
+        writeln!(write, "arithmetic {}", arithmetic_name(self.arithmetic))?;
+        writeln!(write, "input {}", join(self.input.iter()))?;
+        let backend = match &self.memory {
+            Memory::Dense(_) => "dense",
+            Memory::Paged(_) => "paged",
+        };
+        writeln!(write, "memory {}", backend)?;
+        for (addr, words) in self.memory.segments() {
+            writeln!(write, "words {} {}", addr, join(words.iter()))?;
+        }
+        for (addr, value) in &self.wide {
+            writeln!(write, "wide {} {}", addr, value)?;
+        }
+        Ok(())
+    }
+
+    pub fn load<R: BufRead>(read: R) -> Result<Snapshot> {
+        let mut lines = read.lines();
+        if lines.next().transpose()?.as_deref() != Some(HEADER) {
+            Err("not an intcode snapshot")?
+        }
+
+        let (mut pc, mut relative_base, mut memory) = (None, None, None);
+        let mut snapshot = Snapshot {
+            memory: Memory::dense(Vec::new()),
+            wide: BTreeMap::new(),
+            arithmetic: Arithmetic::Checked,
+            input: VecDeque::new(),
+            pc: 0,
+            relative_base: 0,
+            count: 0,
+        };
+        for line in lines {
+            let line = line?;
+            let mut fields = line.splitn(2, ' ');
+            let key = fields.next().unwrap_or("");
+            let value = fields.next().unwrap_or("").trim();
+            match key {
+                "" => {}
+                "pc" => pc = Some(value.parse()?),
+                "relative_base" => relative_base = Some(value.parse()?),
+                "count" => snapshot.count = value.parse()?,
+                "arithmetic" => snapshot.arithmetic = parse_arithmetic(value)?,
+                "input" => snapshot.input = split(value)?.into(),
+                "memory" => {
+                    memory = Some(match value {
+                        "dense" => Memory::dense(Vec::new()),
+                        "paged" => Memory::paged(&[]),
+                        _ => Err(format!("unknown memory backend: {}", value))?,
+                    })
+                }
+                "words" => {
+                    let memory = memory.as_mut().ok_or("words before memory")?;
+                    let (addr, words) = value.split_at(value.find(' ').unwrap_or(value.len()));
+                    let addr = addr.parse::<usize>()?;
+                    let words = split(words)?;
+                    let end = addr.checked_add(words.len()).ok_or("words out of range")?;
+                    if end > addr {
+                        memory.touch(end - 1);
+                    }
+                    for (offset, word) in words.into_iter().enumerate() {
+                        memory.set(addr + offset, word);
+                    }
+                }
+                "wide" => {
+                    let (addr, wide) = value.split_at(value.find(' ').ok_or("missing value")?);
+                    snapshot.wide.insert(addr.parse()?, wide.trim().parse()?);
+                }
+                _ => Err(format!("unknown snapshot field: {}", key))?,
+            }
+        }
+        snapshot.pc = pc.ok_or("snapshot is missing pc")?;
+        snapshot.relative_base = relative_base.ok_or("snapshot is missing relative_base")?;
+        snapshot.memory = memory.ok_or("snapshot is missing memory")?;
+        Ok(snapshot)
+    }
+}
+
+fn arithmetic_name(arithmetic: Arithmetic) -> &'static str {
+    match arithmetic {
+        Arithmetic::Wrapping => "wrapping",
+        Arithmetic::Checked => "checked",
+        Arithmetic::Widening => "widening",
+    }
+}
+
+fn parse_arithmetic(name: &str) -> Result<Arithmetic> {
+    match name {
+        "wrapping" => Ok(Arithmetic::Wrapping),
+        "checked" => Ok(Arithmetic::Checked),
+        "widening" => Ok(Arithmetic::Widening),
+        _ => Err(format!("unknown arithmetic: {}", name))?,
+    }
+}
+
+fn join<'a, I: Iterator<Item = &'a i64>>(values: I) -> String {
+    values
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn split(values: &str) -> Result<Vec<i64>> {
+    let mut result = Vec::new();
+    for value in values.split(',') {
+        let value = value.trim();
+        if !value.is_empty() {
+            result.push(value.parse()?);
+        }
+    }
+    Ok(result)
+}
+
+#[test]
+fn test() {
+    use super::vm::{Status, VM};
+
+    let program = vec![109, 3, 3, 1000, 203, 5000, 4, 1000, 99];
+    let mut vm = VM::with_memory(Memory::paged(&program));
+    vm.feed_inputs(&[7]);
+    assert_eq!(vm.step(&[][..], Vec::new()).unwrap().pc, 2);
+    vm.feed_inputs(&[8, 9]);
+    let mut saved = Vec::new();
+    vm.snapshot().save(&mut saved).unwrap();
+    let snapshot = Snapshot::load(&saved[..]).unwrap();
+    assert_eq!(snapshot, vm.snapshot());
+
+    let mut output = Vec::new();
+    let suspension = VM::restore(snapshot).resume(&[][..], &mut output).unwrap();
+    assert_eq!((suspension.status, suspension.count), (Status::Halt, 4));
+    assert_eq!(output, vec![7]);
+
+    let vm = VM::new(vec![99, 0, 0]);
+    let mut saved = Vec::new();
+    vm.snapshot().save(&mut saved).unwrap();
+    let snapshot = Snapshot::load(&saved[..]).unwrap();
+    assert_eq!((snapshot.memory.extent(), snapshot), (3, vm.snapshot()));
+
+    let truncated = &saved[..saved.iter().position(|&b| b == b'\n').unwrap() + 1];
+    assert!(Snapshot::load(truncated).is_err());
+    let overflow = format!(
+        "{}\npc 0\nrelative_base 0\nmemory dense\nwords {} 1,2\n",
+        HEADER,
+        usize::MAX
+    );
+    assert!(Snapshot::load(overflow.as_bytes()).is_err());
+    let far = format!(
+        "{}\npc 0\nrelative_base 0\nmemory dense\nwords 100000000000 1\n",
+        HEADER
+    );
+    assert_eq!(
+        Snapshot::load(far.as_bytes()).unwrap().memory[100_000_000_000],
+        1
+    );
+}
@@ -0,0 +1,226 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+const NOUN: usize = 1;
+const VERB: usize = 2;
+
+// Straight-line programs only ever advance, so this is a guard against bad jumps rather than
+// a real limit.
+const MAX_STEPS: usize = 100_000;
+
+// A polynomial in noun and verb, keyed by their exponents.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    terms: BTreeMap<(u32, u32), i128>,
+}
+
+impl Expr {
+    pub fn constant(value: i128) -> Expr {
+        Expr::term((0, 0), value)
+    }
+
+    pub fn noun() -> Expr {
+        Expr::term((1, 0), 1)
+    }
+
+    pub fn verb() -> Expr {
+        Expr::term((0, 1), 1)
+    }
+
+    fn term(powers: (u32, u32), coefficient: i128) -> Expr {
+        let mut terms = BTreeMap::new();
+        if coefficient != 0 {
+            terms.insert(powers, coefficient);
+        }
+        Expr { terms }
+    }
+
+    pub fn as_constant(&self) -> Option<i128> {
+        match self.terms.len() {
+            0 => Some(0),
+            1 => self.terms.get(&(0, 0)).copied(),
+            _ => None,
+        }
+    }
+
+    pub fn checked_add(&self, other: &Expr) -> Option<Expr> {
+        let mut terms = self.terms.clone();
+        for (&powers, &coefficient) in &other.terms {
+            let sum = terms.get(&powers).unwrap_or(&0).checked_add(coefficient)?;
+            if sum == 0 {
+                terms.remove(&powers);
+            } else {
+                terms.insert(powers, sum);
+            }
+        }
+        Some(Expr { terms })
+    }
+
+    pub fn checked_mul(&self, other: &Expr) -> Option<Expr> {
+        let mut product = Expr::constant(0);
+        for (&(a, b), &x) in &self.terms {
+            for (&(c, d), &y) in &other.terms {
+                let powers = (a.checked_add(c)?, b.checked_add(d)?);
+                product = product.checked_add(&Expr::term(powers, x.checked_mul(y)?))?;
+            }
+        }
+        Some(product)
+    }
+
+    pub fn evaluate(&self, noun: i64, verb: i64) -> Option<i128> {
+        let mut sum = 0_i128;
+        for (&(a, b), &coefficient) in &self.terms {
+            let term = (noun as i128)
+                .checked_pow(a)?
+                .checked_mul((verb as i128).checked_pow(b)?)?
+                .checked_mul(coefficient)?;
+            sum = sum.checked_add(term)?;
+        }
+        Some(sum)
+    }
+
+    pub fn solve(&self, target: i64, range: RangeInclusive<i64>) -> Option<(i64, i64)> {
+        let linear_in_verb = self.terms.keys().all(|&(a, b)| b == 0 || (a, b) == (0, 1));
+        let slope = self.terms.get(&(0, 1)).copied().unwrap_or(0);
+        for noun in range.clone() {
+            if !linear_in_verb {
+                let found = range
+                    .clone()
+                    .find(|&verb| self.evaluate(noun, verb) == Some(target as i128));
+                if let Some(verb) = found {
+                    return Some((noun, verb));
+                }
+                continue;
+            }
+            let rest = target as i128 - self.evaluate(noun, 0)?;
+            let verb = match (slope, rest) {
+                (0, 0) => *range.start() as i128,
+                (0, _) => continue,
+                _ if rest % slope != 0 => continue,
+                _ => rest / slope,
+            };
+            if verb == verb as i64 as i128 && range.contains(&(verb as i64)) {
+                return Some((noun, verb as i64));
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+        for (idx, (&(a, b), &coefficient)) in self.terms.iter().rev().enumerate() {
+            let sign = match (idx, coefficient < 0) {
+                (0, false) => "",
+                (0, true) => "-",
+                (_, false) => " + ",
+                (_, true) => " - ",
+            };
+            let mut factors = Vec::new();
+            if coefficient.abs() != 1 || (a, b) == (0, 0) {
+                factors.push(coefficient.abs().to_string());
+            }
+            for (name, power) in &[("noun", a), ("verb", b)] {
+                match power {
+                    0 => {}
+                    1 => factors.push(name.to_string()),
+                    _ => factors.push(format!("{}^{}", name, power)),
+                }
+            }
+            write!(f, "{}{}", sign, factors.join("*"))?;
+        }
+        Ok(())
+    }
+}
+
+// Gives up once noun or verb is used as an address or opcode.
+pub fn evaluate(program: &[i64]) -> Option<Expr> {
+    let mut memory: Vec<Option<Expr>> = program
+        .iter()
+        .map(|&word| Some(Expr::constant(word as i128)))
+        .collect();
+    if memory.len() <= VERB {
+        return None;
+    }
+    memory[NOUN] = Some(Expr::noun());
+    memory[VERB] = Some(Expr::verb());
+
+    let word = |memory: &[Option<Expr>], addr: usize| -> Option<Expr> {
+        match memory.get(addr) {
+            Some(value) => value.clone(),
+            None => Some(Expr::constant(0)),
+        }
+    };
+    let address = |memory: &[Option<Expr>], addr: usize| -> Option<usize> {
+        let value = word(memory, addr)?.as_constant()?;
+        if value < 0 {
+            None
+        } else {
+            Some(value as usize)
+        }
+    };
+
+    let mut pc = 0;
+    for _ in 0..MAX_STEPS {
+        let command = word(&memory, pc)?.as_constant()?;
+        if command == 99 {
+            return memory.first().cloned().flatten();
+        }
+        let opcode = command % 100;
+        if command < 0 || !matches!(opcode, 1 | 2) {
+            return None;
+        }
+        let mut operands = Vec::new();
+        for idx in 0..2 {
+            let addr = pc + 1 + idx;
+            let operand = match command / 10_i128.pow(idx as u32 + 2) % 10 {
+                0 => match word(&memory, addr)?.as_constant() {
+                    Some(target) if target >= 0 => word(&memory, target as usize),
+                    Some(_) => return None,
+                    None => None,
+                },
+                1 => word(&memory, addr),
+                _ => return None,
+            };
+            operands.push(operand);
+        }
+        if command / 10_000 % 10 != 0 {
+            return None;
+        }
+        let target = address(&memory, pc + 3)?;
+        let value = match (&operands[0], &operands[1], opcode) {
+            (Some(a), Some(b), 1) => Some(a.checked_add(b)?),
+            (Some(a), Some(b), 2) => Some(a.checked_mul(b)?),
+            _ => None,
+        };
+        if target >= memory.len() {
+            memory.resize(target + 1, Some(Expr::constant(0)));
+        }
+        memory[target] = value;
+        pc += 4;
+    }
+    None
+}
+
+#[test]
+fn test() {
+    let input = include_bytes!("../../input/02");
+    let program = super::get_program(&input[..]).unwrap();
+    let expr = evaluate(&program).unwrap();
+    assert_eq!(expr.to_string(), "324000*noun + verb + 574684");
+    assert_eq!(expr.evaluate(12, 2), Some(4462686));
+    assert_eq!(expr.solve(19690720, 0..=99), Some((59, 36)));
+    assert_eq!(expr.solve(-1, 0..=99), None);
+
+    let expr = evaluate(&[1, 0, 0, 3, 2, 1, 2, 0, 1, 0, 1, 0, 99]).unwrap();
+    assert_eq!(expr.to_string(), "noun*verb + noun");
+    assert_eq!(expr.solve(12, 0..=99), Some((1, 11)));
+    // Noun and verb used as addresses, with the result landing in 0 or on an opcode.
+    assert_eq!(evaluate(&[1, 0, 0, 0, 99]), None);
+    assert_eq!(evaluate(&[1, 0, 0, 4, 1, 0, 0, 0, 99]), None);
+    assert_eq!(evaluate(&[100, 0, 0, 0, 99]), None);
+}
@@ -0,0 +1,377 @@
+use std::fmt;
+use std::io::prelude::*;
+
+use super::error::VmErrorKind;
+use super::instruction::Instruction;
+use super::varint::{read_varint, write_varint};
+use super::Result;
+
+const MAGIC: &[u8] = b"ICTR\x01";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Binary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryWrite {
+    pub addr: usize,
+    pub old: i64,
+    pub new: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub count: u64,
+    pub pc: usize,
+    pub instruction: Instruction,
+    pub operands: Vec<i128>,
+    pub write: Option<MemoryWrite>,
+    pub input: Option<i64>,
+    pub output: Option<i64>,
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{} {:04}: {}", self.count, self.pc, self.instruction)?;
+        if !self.operands.is_empty() {
+            let operands: Vec<_> = self.operands.iter().map(i128::to_string).collect();
+            write!(f, " ({})", operands.join(", "))?;
+        }
+        if let Some(write) = self.write {
+            write!(f, " [{}] {} -> {}", write.addr, write.old, write.new)?;
+        }
+        if let Some(input) = self.input {
+            write!(f, " in {}", input)?;
+        }
+        if let Some(output) = self.output {
+            write!(f, " out {}", output)?;
+        }
+        Ok(())
+    }
+}
+
+pub trait TraceSink {
+    fn trace(&mut self, record: Record) -> std::result::Result<(), VmErrorKind>;
+}
+
+impl<T: TraceSink + ?Sized> TraceSink for &mut T {
+    fn trace(&mut self, record: Record) -> std::result::Result<(), VmErrorKind> {
+        (**self).trace(record)
+    }
+}
+
+impl TraceSink for Vec<Record> {
+    fn trace(&mut self, record: Record) -> std::result::Result<(), VmErrorKind> {
+        self.push(record);
+        Ok(())
+    }
+}
+
+pub struct TraceWriter<W: Write> {
+    write: W,
+    format: Format,
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(mut write: W, format: Format) -> Result<TraceWriter<W>> {
+        if format == Format::Binary {
+            write.write_all(MAGIC)?;
+        }
+        Ok(TraceWriter { write, format })
+    }
+
+    pub fn record(&mut self, record: &Record) -> Result<()> {
+        match self.format {
+            Format::Json => write_json(&mut self.write, record),
+            Format::Binary => write_binary(&mut self.write, record),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.write
+    }
+}
+
+impl<W: Write> TraceSink for TraceWriter<W> {
+    fn trace(&mut self, record: Record) -> std::result::Result<(), VmErrorKind> {
+        self.record(&record)
+            .map_err(|err| VmErrorKind::Io(err.to_string()))
+    }
+}
+
+pub fn read_trace<R: BufRead>(mut read: R) -> Result<Vec<Record>> {
+    let binary = read.fill_buf()?.starts_with(MAGIC);
+    let mut records = Vec::new();
+    if binary {
+        read.consume(MAGIC.len());
+        while let Some(record) = read_binary(&mut read)? {
+            records.push(record);
+        }
+    } else {
+        for line in read.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                records.push(read_json(&line)?);
+            }
+        }
+    }
+    Ok(records)
+}
+
+fn write_json<W: Write>(write: &mut W, record: &Record) -> Result<()> {
+    let words: Vec<_> = record
+        .instruction
+        .encode()
+        .iter()
+        .map(i64::to_string)
+        .collect();
+    let operands: Vec<_> = record.operands.iter().map(i128::to_string).collect();
+    write!(
+        write,
+        "{{\"count\":{},\"pc\":{},\"words\":[{}],\"instruction\":\"{}\",\"operands\":[{}]",
+        record.count,
+        record.pc,
+        words.join(","),
+        record.instruction,
+        operands.join(",")
+    )?;
+    if let Some(w) = record.write {
+        let (addr, old, new) = (w.addr, w.old, w.new);
+        write!(
+            write,
+            ",\"write\":{{\"addr\":{},\"old\":{},\"new\":{}}}",
+            addr, old, new
+        )?;
+    }
+    if let Some(input) = record.input {
+        write!(write, ",\"input\":{}", input)?;
+    }
+    if let Some(output) = record.output {
+        write!(write, ",\"output\":{}", output)?;
+    }
+    writeln!(write, "}}")?;
+    Ok(())
+}
+
+// Only the flat shape written above is understood: numbers, plain strings, arrays of numbers
+// and one level of nested object.
+fn read_json(line: &str) -> Result<Record> {
+    let mut fields = Fields(line.trim());
+    fields.expect('{')?;
+    let mut count = None;
+    let mut pc = None;
+    let mut words = None;
+    let mut operands = Vec::new();
+    let mut write = None;
+    let mut input = None;
+    let mut output = None;
+    while !fields.eat('}') {
+        let key = fields.string()?;
+        fields.expect(':')?;
+        match key {
+            "count" => count = Some(fields.number()? as u64),
+            "pc" => pc = Some(fields.number()? as usize),
+            "words" => words = Some(fields.numbers()?),
+            "instruction" => {
+                fields.string()?;
+            }
+            "operands" => operands = fields.numbers()?,
+            "write" => {
+                let mut w = MemoryWrite {
+                    addr: 0,
+                    old: 0,
+                    new: 0,
+                };
+                fields.expect('{')?;
+                while !fields.eat('}') {
+                    let key = fields.string()?;
+                    fields.expect(':')?;
+                    let value = fields.number()?;
+                    match key {
+                        "addr" => w.addr = value as usize,
+                        "old" => w.old = value as i64,
+                        "new" => w.new = value as i64,
+                        _ => Err(format!("unknown write field: {}", key))?,
+                    }
+                    fields.eat(',');
+                }
+                write = Some(w);
+            }
+            "input" => input = Some(fields.number()? as i64),
+            "output" => output = Some(fields.number()? as i64),
+            _ => Err(format!("unknown trace field: {}", key))?,
+        }
+        fields.eat(',');
+    }
+
+    let words: Vec<i64> = words
+        .ok_or("missing words")?
+        .into_iter()
+        .map(|w| w as i64)
+        .collect();
+    Ok(Record {
+        count: count.ok_or("missing count")?,
+        pc: pc.ok_or("missing pc")?,
+        instruction: Instruction::from_slice(&words).map_err(|kind| kind.to_string())?,
+        operands,
+        write,
+        input,
+        output,
+    })
+}
+
+struct Fields<'a>(&'a str);
+
+impl<'a> Fields<'a> {
+    fn eat(&mut self, c: char) -> bool {
+        self.0 = self.0.trim_start();
+        match self.0.strip_prefix(c) {
+            Some(rest) => {
+                self.0 = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        if !self.eat(c) {
+            Err(format!("expected {:?} at {:?}", c, self.0))?
+        }
+        Ok(())
+    }
+
+    fn string(&mut self) -> Result<&'a str> {
+        self.expect('"')?;
+        let end = self.0.find('"').ok_or("unterminated string")?;
+        let value = &self.0[..end];
+        self.0 = &self.0[end + 1..];
+        Ok(value)
+    }
+
+    fn number(&mut self) -> Result<i128> {
+        self.0 = self.0.trim_start();
+        let end = self.0.find([',', ']', '}']).unwrap_or(self.0.len());
+        let value = self.0[..end].trim().parse()?;
+        self.0 = &self.0[end..];
+        Ok(value)
+    }
+
+    fn numbers(&mut self) -> Result<Vec<i128>> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        while !self.eat(']') {
+            values.push(self.number()?);
+            self.eat(',');
+        }
+        Ok(values)
+    }
+}
+
+fn write_binary<W: Write>(write: &mut W, record: &Record) -> Result<()> {
+    let words = record.instruction.encode();
+    let mut buf = Vec::new();
+    write_varint(&mut buf, record.count as i128);
+    write_varint(&mut buf, record.pc as i128);
+    write_varint(&mut buf, words.len() as i128);
+    for &word in &words {
+        write_varint(&mut buf, word as i128);
+    }
+    write_varint(&mut buf, record.operands.len() as i128);
+    for &operand in &record.operands {
+        write_varint(&mut buf, operand);
+    }
+    let flags = record.write.is_some() as i128
+        | (record.input.is_some() as i128) << 1
+        | (record.output.is_some() as i128) << 2;
+    write_varint(&mut buf, flags);
+    if let Some(w) = record.write {
+        write_varint(&mut buf, w.addr as i128);
+        write_varint(&mut buf, w.old as i128);
+        write_varint(&mut buf, w.new as i128);
+    }
+    for value in record.input.iter().chain(record.output.iter()) {
+        write_varint(&mut buf, *value as i128);
+    }
+    write.write_all(&buf)?;
+    Ok(())
+}
+
+fn read_binary<R: BufRead>(read: &mut R) -> Result<Option<Record>> {
+    let count = match read_varint(read)? {
+        Some(count) => count as u64,
+        None => return Ok(None),
+    };
+    let mut next = || -> Result<i128> { Ok(read_varint(read)?.ok_or("truncated trace")?) };
+    let pc = next()? as usize;
+    let mut words = Vec::new();
+    for _ in 0..next()? {
+        words.push(next()? as i64);
+    }
+    let mut operands = Vec::new();
+    for _ in 0..next()? {
+        operands.push(next()?);
+    }
+    let flags = next()?;
+    let write = if flags & 1 != 0 {
+        Some(MemoryWrite {
+            addr: next()? as usize,
+            old: next()? as i64,
+            new: next()? as i64,
+        })
+    } else {
+        None
+    };
+    let input = if flags & 2 != 0 {
+        Some(next()? as i64)
+    } else {
+        None
+    };
+    let output = if flags & 4 != 0 {
+        Some(next()? as i64)
+    } else {
+        None
+    };
+    Ok(Some(Record {
+        count,
+        pc,
+        instruction: Instruction::from_slice(&words).map_err(|kind| kind.to_string())?,
+        operands,
+        write,
+        input,
+        output,
+    }))
+}
+
+#[test]
+fn test() {
+    use super::vm::VM;
+
+    let mut vm = VM::new(vec![3, 9, 1002, 9, -3, 9, 4, 9, 99, 0]);
+    let mut trace = Vec::new();
+    vm.resume_traced(&[7][..], Vec::new(), &mut trace).unwrap();
+    assert_eq!(trace.len(), 4);
+    assert_eq!(
+        trace[1].to_string(),
+        "#1 0002: MUL [9], #-3 -> [9] (7, -3) [9] 7 -> -21"
+    );
+    assert_eq!((trace[0].input, trace[2].output), (Some(7), Some(-21)));
+
+    for &format in &[Format::Json, Format::Binary] {
+        let mut writer = TraceWriter::new(Vec::new(), format).unwrap();
+        for record in &trace {
+            writer.record(record).unwrap();
+        }
+        let bytes = writer.into_inner();
+        assert_eq!(read_trace(&bytes[..]).unwrap(), trace);
+    }
+    let mut json = Vec::new();
+    write_json(&mut json, &trace[0]).unwrap();
+    assert_eq!(
+        String::from_utf8(json).unwrap(),
+        "{\"count\":0,\"pc\":0,\"words\":[3,9],\"instruction\":\"IN -> [9]\",\"operands\":[],\
+         \"write\":{\"addr\":9,\"old\":0,\"new\":7},\"input\":7}\n"
+    );
+}
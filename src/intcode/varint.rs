@@ -0,0 +1,51 @@
+use std::io::prelude::*;
+
+use super::Result;
+
+pub fn write_varint(buf: &mut Vec<u8>, value: i128) {
+    let mut zigzag = ((value << 1) ^ (value >> 127)) as u128;
+    while zigzag >= 0x80 {
+        buf.push(zigzag as u8 | 0x80);
+        zigzag >>= 7;
+    }
+    buf.push(zigzag as u8);
+}
+
+pub fn read_varint<R: Read>(read: &mut R) -> Result<Option<i128>> {
+    let mut zigzag = 0u128;
+    let mut shift = 0;
+    let mut byte = [0];
+    loop {
+        if read.read(&mut byte)? == 0 {
+            if shift == 0 {
+                return Ok(None);
+            }
+            Err("truncated varint")?
+        }
+        if shift >= 128 {
+            Err("varint too long")?
+        }
+        zigzag |= ((byte[0] & 0x7f) as u128) << shift;
+        shift += 7;
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+    }
+    Ok(Some((zigzag >> 1) as i128 ^ -((zigzag & 1) as i128)))
+}
+
+#[test]
+fn test() {
+    let mut buf = Vec::new();
+    for &value in &[0, -1, 1, 63, -64, 64, i128::MAX, i128::MIN] {
+        write_varint(&mut buf, value);
+    }
+    assert_eq!(&buf[..5], &[0, 1, 2, 126, 127]);
+    let mut read = &buf[..];
+    let mut values = Vec::new();
+    while let Some(value) = read_varint(&mut read).unwrap() {
+        values.push(value);
+    }
+    assert_eq!(values, vec![0, -1, 1, 63, -64, 64, i128::MAX, i128::MIN]);
+    assert!(read_varint(&mut &[0x80][..]).is_err());
+}
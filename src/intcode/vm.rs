@@ -0,0 +1,917 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::convert::TryFrom;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Instant;
+
+use super::coverage::Coverage;
+use super::error::{VmError, VmErrorKind};
+use super::instruction::{Instruction, Param};
+use super::io::{IntcodeInput, IntcodeOutput};
+use super::memory::Memory;
+use super::profile::Profile;
+use super::selfmod::SelfModification;
+use super::snapshot::Snapshot;
+use super::trace::{MemoryWrite, Record, TraceSink};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    SuspendInput,
+    Halt,
+    Output(i64),
+    Budget,
+    Breakpoint,
+    Watchdog(Limit),
+    Watchpoint(Watch),
+    StartOfHistory,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    Instructions,
+    Deadline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Access {
+    fn covers(self, access: Access) -> bool {
+        self == Access::ReadWrite || self == access
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Watch {
+    pub pc: usize,
+    pub instruction: Instruction,
+    pub addr: usize,
+    pub access: Access,
+    pub old: i64,
+    pub new: i64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Undo {
+    pc: usize,
+    relative_base: i64,
+    count: u64,
+    write: Option<(usize, i64, Option<i128>)>,
+    input: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Suspension {
+    pub status: Status,
+    pub pc: usize,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arithmetic {
+    Wrapping,
+    Checked,
+    Widening,
+}
+
+// A program decoded once up front, shared by every VM that runs it.
+#[derive(Debug)]
+pub struct DecodeCache {
+    words: Vec<i64>,
+    decoded: Vec<Option<Instruction>>,
+}
+
+impl DecodeCache {
+    pub fn new(program: &[i64]) -> DecodeCache {
+        let memory = Memory::dense(program.to_vec());
+        let decoded = (0..program.len())
+            .map(|pc| Instruction::from_slice(&memory.fetch(pc)).ok())
+            .collect();
+        // Padded so every decoded pc has four words to compare against.
+        let mut words = program.to_vec();
+        words.extend_from_slice(&[0; 3]);
+        DecodeCache { words, decoded }
+    }
+
+    fn program(&self) -> Vec<i64> {
+        self.words[..self.decoded.len()].to_vec()
+    }
+
+    #[inline]
+    fn decode(&self, pc: usize, words: &[i64; 4]) -> Result<Instruction, VmErrorKind> {
+        // Comparing the words catches the program having rewritten itself since.
+        match self.decoded.get(pc) {
+            Some(Some(inst)) if self.words[pc..pc + 4] == *words => Ok(*inst),
+            _ => Instruction::from_slice(words),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct VM {
+    memory: Memory,
+    wide: BTreeMap<usize, i128>,
+    arithmetic: Arithmetic,
+    input: VecDeque<i64>,
+    pc: usize,
+    relative_base: i64,
+    count: u64,
+    yield_on_output: bool,
+    budget: Option<u64>,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: Vec<(Range<usize>, Access)>,
+    max_instructions: Option<u64>,
+    deadline: Option<Instant>,
+    decode_cache: Option<Arc<DecodeCache>>,
+    history_limit: Option<usize>,
+    history: VecDeque<Undo>,
+    profile: Option<Profile>,
+    coverage: Option<Coverage>,
+    self_modification: Option<SelfModification>,
+}
+
+impl VM {
+    pub fn new(memory: Vec<i64>) -> VM {
+        VM::with_memory(Memory::dense(memory))
+    }
+
+    pub fn with_memory(memory: Memory) -> VM {
+        VM {
+            memory,
+            wide: BTreeMap::new(),
+            arithmetic: Arithmetic::Checked,
+            input: VecDeque::new(),
+            pc: 0,
+            relative_base: 0,
+            count: 0,
+            yield_on_output: false,
+            budget: None,
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+            max_instructions: None,
+            deadline: None,
+            decode_cache: None,
+            history_limit: None,
+            history: VecDeque::new(),
+            profile: None,
+            coverage: None,
+            self_modification: None,
+        }
+    }
+
+    pub fn with_decode_cache(cache: Arc<DecodeCache>) -> VM {
+        let mut vm = VM::new(cache.program());
+        vm.decode_cache = Some(cache);
+        vm
+    }
+
+    pub fn restore(snapshot: Snapshot) -> VM {
+        let mut vm = VM::with_memory(snapshot.memory);
+        vm.wide = snapshot.wide;
+        vm.arithmetic = snapshot.arithmetic;
+        vm.input = snapshot.input;
+        vm.pc = snapshot.pc;
+        vm.relative_base = snapshot.relative_base;
+        vm.count = snapshot.count;
+        vm
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.memory.clone(),
+            wide: self.wide.clone(),
+            arithmetic: self.arithmetic,
+            input: self.input.clone(),
+            pc: self.pc,
+            relative_base: self.relative_base,
+            count: self.count,
+        }
+    }
+
+    pub fn set_arithmetic(&mut self, arithmetic: Arithmetic) {
+        self.arithmetic = arithmetic;
+    }
+
+    pub fn set_yield_on_output(&mut self, yield_on_output: bool) {
+        self.yield_on_output = yield_on_output;
+    }
+
+    pub fn set_budget(&mut self, budget: Option<u64>) {
+        self.budget = budget;
+    }
+
+    pub fn set_max_instructions(&mut self, max_instructions: Option<u64>) {
+        self.max_instructions = max_instructions;
+    }
+
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    pub fn set_profile(&mut self, profile: bool) {
+        self.profile = if profile { Some(Profile::new()) } else { None };
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        let fresh = self.profile.as_ref().map(|_| Profile::new());
+        std::mem::replace(&mut self.profile, fresh)
+    }
+
+    pub fn set_coverage(&mut self, coverage: bool) {
+        self.coverage = if coverage {
+            Some(Coverage::new())
+        } else {
+            None
+        };
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        let fresh = self.coverage.as_ref().map(|_| Coverage::new());
+        std::mem::replace(&mut self.coverage, fresh)
+    }
+
+    pub fn set_self_modification(&mut self, detect: bool) {
+        self.self_modification = if detect {
+            Some(SelfModification::new())
+        } else {
+            None
+        };
+    }
+
+    pub fn self_modification(&self) -> Option<&SelfModification> {
+        self.self_modification.as_ref()
+    }
+
+    pub fn take_self_modification(&mut self) -> Option<SelfModification> {
+        let fresh = self
+            .self_modification
+            .as_ref()
+            .map(|_| SelfModification::new());
+        std::mem::replace(&mut self.self_modification, fresh)
+    }
+
+    pub fn set_history(&mut self, limit: Option<usize>) {
+        self.history_limit = limit;
+        if let Some(limit) = limit {
+            let excess = self.history.len().saturating_sub(limit);
+            self.history.drain(..excess);
+        } else {
+            self.history.clear();
+        }
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn step_back(&mut self) -> bool {
+        self.undo().is_some()
+    }
+
+    pub fn reverse_continue(&mut self) -> Suspension {
+        let status = loop {
+            let write = match self.undo() {
+                Some(write) => write,
+                None => break Status::StartOfHistory,
+            };
+            if self.breakpoints.contains(&self.pc) {
+                break Status::Breakpoint;
+            }
+            if self.watchpoints.is_empty() {
+                continue;
+            }
+            if let Ok(inst) = Instruction::from_slice(&self.memory.fetch(self.pc)) {
+                let (reads, _) = self.accesses(inst);
+                if let Some(status) = self.watch_hit(self.pc, inst, &reads, write) {
+                    break status;
+                }
+            }
+        };
+        Suspension {
+            status,
+            pc: self.pc,
+            count: self.count,
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, pc: usize) {
+        self.breakpoints.insert(pc);
+    }
+
+    pub fn remove_breakpoint(&mut self, pc: usize) -> bool {
+        self.breakpoints.remove(&pc)
+    }
+
+    pub fn breakpoints(&self) -> &BTreeSet<usize> {
+        &self.breakpoints
+    }
+
+    pub fn add_watchpoint(&mut self, range: Range<usize>, access: Access) {
+        self.watchpoints.push((range, access));
+    }
+
+    pub fn remove_watchpoint(&mut self, range: Range<usize>) -> bool {
+        let before = self.watchpoints.len();
+        self.watchpoints.retain(|(watched, _)| *watched != range);
+        self.watchpoints.len() != before
+    }
+
+    pub fn watchpoints(&self) -> &[(Range<usize>, Access)] {
+        &self.watchpoints
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    pub fn pending_inputs(&self) -> &VecDeque<i64> {
+        &self.input
+    }
+
+    pub fn set_memory(&mut self, addr: usize, value: i64) {
+        self.write(addr, value, value as i128);
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    pub fn get_wide(&self, addr: usize) -> i128 {
+        match self.wide.get(&addr) {
+            Some(value) => *value,
+            None => self.memory[addr] as i128,
+        }
+    }
+
+    pub fn feed_inputs(&mut self, inputs: &[i64]) {
+        self.input.extend(inputs)
+    }
+
+    pub fn resume<I: IntcodeInput, O: IntcodeOutput>(
+        &mut self,
+        input: I,
+        output: O,
+    ) -> Result<Suspension, VmError> {
+        self.run(input, output, self.budget, true, None)
+    }
+
+    pub fn resume_traced<I: IntcodeInput, O: IntcodeOutput, T: TraceSink>(
+        &mut self,
+        input: I,
+        output: O,
+        mut trace: T,
+    ) -> Result<Suspension, VmError> {
+        self.run(input, output, self.budget, true, Some(&mut trace))
+    }
+
+    pub fn step<I: IntcodeInput, O: IntcodeOutput>(
+        &mut self,
+        input: I,
+        output: O,
+    ) -> Result<Suspension, VmError> {
+        self.run(input, output, Some(1), false, None)
+    }
+
+    fn run<I: IntcodeInput, O: IntcodeOutput>(
+        &mut self,
+        mut input: I,
+        mut output: O,
+        budget: Option<u64>,
+        breakpoints: bool,
+        mut trace: Option<&mut (dyn TraceSink + '_)>,
+    ) -> Result<Suspension, VmError> {
+        let start = self.count;
+        let status = loop {
+            let executed = self.count - start;
+            if self.max_instructions.is_some_and(|max| self.count >= max) {
+                break Status::Watchdog(Limit::Instructions);
+            }
+            // Reading the clock is slow next to an instruction, so only look every so often.
+            if let Some(deadline) = self.deadline {
+                if executed.is_multiple_of(1024) && Instant::now() >= deadline {
+                    break Status::Watchdog(Limit::Deadline);
+                }
+            }
+            if budget == Some(executed) {
+                break Status::Budget;
+            }
+            if breakpoints && executed > 0 && self.breakpoints.contains(&self.pc) {
+                break Status::Breakpoint;
+            }
+            let pc = self.pc;
+            let result = match self.decode() {
+                Ok(inst) if trace.is_some() || self.instrumented() => {
+                    let trace = trace.as_deref_mut();
+                    self.exec_instrumented(pc, inst, &mut input, &mut output, trace)
+                }
+                Ok(inst) => self.exec(inst, &mut input, &mut output),
+                Err(kind) => Err(kind),
+            };
+            match result {
+                Ok(None) => {}
+                Ok(Some(status)) => break status,
+                Err(kind) => {
+                    return Err(VmError {
+                        pc,
+                        opcode: self.memory[pc],
+                        kind,
+                    });
+                }
+            }
+        };
+        Ok(Suspension {
+            status,
+            pc: self.pc,
+            count: self.count,
+        })
+    }
+
+    fn exec<I: IntcodeInput, O: IntcodeOutput>(
+        &mut self,
+        inst: Instruction,
+        input: &mut I,
+        output: &mut O,
+    ) -> Result<Option<Status>, VmErrorKind> {
+        match &inst {
+            Instruction::Halt => return Ok(Some(Status::Halt)),
+            Instruction::Add { a, b, c } => {
+                let value = self.load(a)?.checked_add(self.load(b)?);
+                self.store(c, value.ok_or(VmErrorKind::Overflow)?)?;
+            }
+            Instruction::Mul { a, b, c } => {
+                let value = self.load(a)?.checked_mul(self.load(b)?);
+                self.store(c, value.ok_or(VmErrorKind::Overflow)?)?;
+            }
+            Instruction::Input(pos) => {
+                let value = match self.input.pop_front() {
+                    Some(value) => Some(value),
+                    None => input.read()?,
+                };
+                match value {
+                    Some(value) => self.store(pos, value as i128)?,
+                    None => return Ok(Some(Status::SuspendInput)),
+                }
+            }
+            Instruction::Output(param) => {
+                let value = i64::try_from(self.load(param)?);
+                let value = value.map_err(|_| VmErrorKind::Overflow)?;
+                output.write(value)?;
+                if self.yield_on_output {
+                    self.count += 1;
+                    self.pc += inst.pc_offset();
+                    return Ok(Some(Status::Output(value)));
+                }
+            }
+            Instruction::JumpIfTrue { cond, next } => {
+                if self.load(cond)? != 0 {
+                    self.pc = jump_target(self.load(next)?)?;
+                    self.count += 1;
+                    return Ok(None);
+                }
+            }
+            Instruction::JumpIfFalse { cond, next } => {
+                if self.load(cond)? == 0 {
+                    self.pc = jump_target(self.load(next)?)?;
+                    self.count += 1;
+                    return Ok(None);
+                }
+            }
+            Instruction::LessThan { a, b, c } => {
+                let value = if self.load(a)? < self.load(b)? { 1 } else { 0 };
+                self.store(c, value)?;
+            }
+            Instruction::Eq { a, b, c } => {
+                let value = if self.load(a)? == self.load(b)? { 1 } else { 0 };
+                self.store(c, value)?;
+            }
+            Instruction::AdjustRelativeBase(param) => {
+                let value = i64::try_from(self.load(param)?)
+                    .ok()
+                    .and_then(|value| self.relative_base.checked_add(value));
+                self.relative_base = value.ok_or(VmErrorKind::Overflow)?;
+            }
+        }
+        self.count += 1;
+        self.pc += inst.pc_offset();
+        Ok(None)
+    }
+
+    fn instrumented(&self) -> bool {
+        self.profile.is_some()
+            || self.coverage.is_some()
+            || self.self_modification.is_some()
+            || self.history_limit.is_some_and(|limit| limit > 0)
+            || !self.watchpoints.is_empty()
+    }
+
+    // Returns the write that was reverted as (addr, restored, overwritten). Input the
+    // instruction consumed goes back on the pending queue, but output it produced can't be
+    // taken back and will be written again on replay.
+    fn undo(&mut self) -> Option<Option<(usize, i64, i64)>> {
+        let undo = self.history.pop_back()?;
+        let write = undo.write.map(|(addr, old, wide)| {
+            let new = self.memory[addr];
+            self.write(addr, old, wide.unwrap_or(old as i128));
+            (addr, old, new)
+        });
+        if let Some(input) = undo.input {
+            self.input.push_front(input);
+        }
+        self.pc = undo.pc;
+        self.relative_base = undo.relative_base;
+        self.count = undo.count;
+        Some(write)
+    }
+
+    fn exec_instrumented<I: IntcodeInput, O: IntcodeOutput>(
+        &mut self,
+        pc: usize,
+        inst: Instruction,
+        input: &mut I,
+        output: &mut O,
+        trace: Option<&mut (dyn TraceSink + '_)>,
+    ) -> Result<Option<Status>, VmErrorKind> {
+        let record = trace.as_ref().map(|_| self.begin_record(pc, inst));
+        let (reads, target) = self.accesses(inst);
+        let undo = Undo {
+            pc,
+            relative_base: self.relative_base,
+            count: self.count,
+            write: target.map(|addr| (addr, self.memory[addr], self.wide.get(&addr).copied())),
+            input: None,
+        };
+        let taken = match (&self.profile, inst) {
+            (None, _) => None,
+            (_, Instruction::JumpIfTrue { cond, .. }) => self.load(&cond).ok().map(|v| v != 0),
+            (_, Instruction::JumpIfFalse { cond, .. }) => self.load(&cond).ok().map(|v| v == 0),
+            _ => None,
+        };
+
+        let status = self.exec(inst, input, output)?;
+        if self.count == undo.count && status != Some(Status::Halt) {
+            return Ok(status);
+        }
+        if let (Some(trace), Some(record)) = (trace, record) {
+            trace.trace(self.finish_record(record))?;
+        }
+        // A halt is traced and covered, but like the instruction count, the profile leaves it out.
+        let executed = self.count != undo.count;
+        if let Some(profile) = self.profile.as_mut().filter(|_| executed) {
+            profile.record(pc, &inst, taken);
+        }
+        if let Some(coverage) = &mut self.coverage {
+            let reads: Vec<usize> = reads.iter().map(|&(addr, _)| addr).collect();
+            coverage.record(pc, &inst, &reads, target);
+        }
+        if let Some(self_modification) = &mut self.self_modification {
+            self_modification.record(pc, &inst, target);
+        }
+        let write = undo
+            .write
+            .map(|(addr, old, _)| (addr, old, self.memory[addr]));
+        let limit = self.history_limit.filter(|&limit| limit > 0);
+        if let Some(limit) = limit.filter(|_| self.count != undo.count) {
+            if self.history.len() >= limit {
+                self.history.pop_front();
+            }
+            let input = match inst {
+                Instruction::Input(_) => write.map(|(_, _, new)| new),
+                _ => None,
+            };
+            self.history.push_back(Undo { input, ..undo });
+        }
+
+        // A watchpoint reports after the instruction completes, so old and new values are
+        // both known.
+        Ok(self.watch_hit(pc, inst, &reads, write).or(status))
+    }
+
+    // Operand addresses are known up front, which keeps the watchpoint checks out of load and
+    // store. Every memory operand counts as read, even a jump target that ends up unused.
+    fn accesses(&self, inst: Instruction) -> (Vec<(usize, i64)>, Option<usize>) {
+        let mut params = inst.params();
+        let target = inst.target().and_then(|_| params.pop());
+        let target = target.and_then(|target| target.address(self.relative_base).ok());
+        let reads = params
+            .iter()
+            .filter(|param| !matches!(param, Param::Immediate(_)))
+            .filter_map(|param| param.address(self.relative_base).ok())
+            .map(|addr| (addr, self.memory[addr]))
+            .collect();
+        (reads, target)
+    }
+
+    // The first access an instruction makes wins.
+    fn watch_hit(
+        &self,
+        pc: usize,
+        inst: Instruction,
+        reads: &[(usize, i64)],
+        write: Option<(usize, i64, i64)>,
+    ) -> Option<Status> {
+        let (addr, access, old, new) = reads
+            .iter()
+            .map(|&(addr, value)| (addr, Access::Read, value, value))
+            .chain(write.map(|(addr, old, new)| (addr, Access::Write, old, new)))
+            .find(|&(addr, access, _, _)| self.watched(addr, access))?;
+        Some(Status::Watchpoint(Watch {
+            pc,
+            instruction: inst,
+            addr,
+            access,
+            old,
+            new,
+        }))
+    }
+
+    // Operands are resolved before the instruction runs, since it may overwrite one of them.
+    fn begin_record(&self, pc: usize, inst: Instruction) -> Record {
+        let mut params = inst.params();
+        let target = inst.target().and_then(|_| params.pop());
+        let write = target
+            .and_then(|target| target.address(self.relative_base).ok())
+            .map(|addr| MemoryWrite {
+                addr,
+                old: self.memory[addr],
+                new: 0,
+            });
+        Record {
+            count: self.count,
+            pc,
+            instruction: inst,
+            operands: params.iter().filter_map(|p| self.load(p).ok()).collect(),
+            write,
+            input: None,
+            output: None,
+        }
+    }
+
+    fn finish_record(&self, mut record: Record) -> Record {
+        if let Some(write) = &mut record.write {
+            write.new = self.memory[write.addr];
+        }
+        match record.instruction {
+            Instruction::Input(_) => record.input = record.write.map(|write| write.new),
+            Instruction::Output(_) => record.output = Some(record.operands[0] as i64),
+            _ => {}
+        }
+        record
+    }
+
+    #[inline]
+    fn load(&self, param: &Param) -> Result<i128, VmErrorKind> {
+        if self.wide.is_empty() {
+            return Ok(param.get(&self.memory, self.relative_base)? as i128);
+        }
+        match param {
+            Param::Immediate(x) => Ok(*x as i128),
+            param => Ok(self.get_wide(param.address(self.relative_base)?)),
+        }
+    }
+
+    #[inline]
+    fn store(&mut self, param: &Param, value: i128) -> Result<(), VmErrorKind> {
+        let addr = param.address(self.relative_base)?;
+        let narrow = match (i64::try_from(value), self.arithmetic) {
+            (Ok(narrow), _) => narrow,
+            (Err(_), Arithmetic::Checked) => return Err(VmErrorKind::Overflow),
+            (Err(_), _) => value as i64,
+        };
+        self.write(addr, narrow, value);
+        Ok(())
+    }
+
+    fn watched(&self, addr: usize, access: Access) -> bool {
+        let watched = self.watchpoints.iter();
+        watched
+            .filter(|(range, _)| range.contains(&addr))
+            .any(|(_, kind)| kind.covers(access))
+    }
+
+    #[inline]
+    fn write(&mut self, addr: usize, narrow: i64, value: i128) {
+        self.memory.set(addr, narrow);
+        if self.arithmetic == Arithmetic::Widening && narrow as i128 != value {
+            self.wide.insert(addr, value);
+        } else if !self.wide.is_empty() {
+            self.wide.remove(&addr);
+        }
+    }
+
+    #[inline]
+    fn decode(&mut self) -> Result<Instruction, VmErrorKind> {
+        let pc = self.pc;
+        let words = self.memory.fetch(pc);
+        if let Some(cache) = &self.decode_cache {
+            return cache.decode(pc, &words);
+        }
+        Instruction::from_slice(&words)
+    }
+}
+
+fn jump_target(target: i128) -> Result<usize, VmErrorKind> {
+    usize::try_from(target).map_err(|_| VmErrorKind::JumpOutOfBounds(target as i64))
+}
+
+pub struct Execution {
+    pub memory: Memory,
+    pub output: Vec<i64>,
+}
+
+pub fn execute(memory: Vec<i64>, input: &[i64]) -> Result<Execution, VmError> {
+    let mut vm = VM::new(memory);
+    let mut output = Vec::new();
+    match vm.resume(input, &mut output)?.status {
+        Status::SuspendInput => Err(VmError {
+            pc: vm.pc,
+            opcode: vm.memory[vm.pc],
+            kind: VmErrorKind::InputExhausted,
+        }),
+        _ => Ok(Execution {
+            memory: vm.memory,
+            output,
+        }),
+    }
+}
+
+#[test]
+fn test() {
+    let program = vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+    let output = execute(program.clone(), &[8]).unwrap().output;
+    assert_eq!(output, vec![1]);
+    let output = execute(program.clone(), &[7]).unwrap().output;
+    assert_eq!(output, vec![0]);
+    let error = execute(program, &[]).err().unwrap();
+    assert_eq!((error.pc, error.kind), (0, VmErrorKind::InputExhausted));
+
+    let program = vec![109, 7, 203, 0, 204, 0, 99, 0];
+    let execution = execute(program, &[42]).unwrap();
+    assert_eq!(execution.output, vec![42]);
+    assert_eq!(execution.memory[7], 42);
+
+    let program = vec![1101, 1, 2, 1000, 109, 2000, 21101, 3, 4, 0, 204, 0, 99];
+    let mut vm = VM::with_memory(Memory::paged(&program));
+    let mut output = Vec::new();
+    assert_eq!(
+        vm.resume(&[][..], &mut output).unwrap().status,
+        Status::Halt
+    );
+    assert_eq!(output, vec![7]);
+    assert_eq!(vm.memory()[1000], 3);
+    let execution = execute(vec![1101, 1, 1, 1 << 45, 99], &[]).unwrap();
+    assert_eq!(execution.memory[1 << 45], 2);
+
+    let error = execute(vec![1105, 1, -4], &[]).err().unwrap();
+    assert_eq!(
+        error.to_string(),
+        "jump out of bounds to -4 at pc 0 (opcode 1105)"
+    );
+
+    let program = vec![1002, 5, 3, 5, 99, i64::MAX];
+    let error = execute(program.clone(), &[]).err().unwrap();
+    assert_eq!((error.pc, error.kind), (0, VmErrorKind::Overflow));
+    let mut vm = VM::new(program.clone());
+    vm.set_arithmetic(Arithmetic::Wrapping);
+    vm.resume(&[][..], Vec::new()).unwrap();
+    assert_eq!(vm.memory()[5], i64::MAX.wrapping_mul(3));
+    let mut vm = VM::new(program);
+    vm.set_arithmetic(Arithmetic::Widening);
+    vm.resume(&[][..], Vec::new()).unwrap();
+    assert_eq!(vm.get_wide(5), i64::MAX as i128 * 3);
+
+    let program = vec![3, 9, 1002, 9, 2, 9, 4, 9, 99, 0];
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let mut vm = VM::new(program);
+    vm.feed_inputs(&[4]);
+    assert_eq!(vm.resume(&[][..], sender).unwrap().status, Status::Halt);
+    assert_eq!(receiver.recv(), Ok(8));
+
+    let program = vec![104, 1, 104, 2, 1105, 1, 0];
+    let mut vm = VM::new(program);
+    vm.set_yield_on_output(true);
+    let suspension = vm.resume(&[][..], Vec::new()).unwrap();
+    assert_eq!((suspension.status, suspension.pc), (Status::Output(1), 2));
+    vm.set_yield_on_output(false);
+    vm.add_breakpoint(4);
+    let suspension = vm.resume(&[][..], Vec::new()).unwrap();
+    assert_eq!(
+        suspension,
+        Suspension {
+            status: Status::Breakpoint,
+            pc: 4,
+            count: 2
+        }
+    );
+    vm.remove_breakpoint(4);
+    vm.set_budget(Some(10));
+    let suspension = vm.resume(&[][..], Vec::new()).unwrap();
+    assert_eq!((suspension.status, suspension.count), (Status::Budget, 12));
+    let suspension = vm.step(&[][..], Vec::new()).unwrap();
+    assert_eq!((suspension.status, suspension.pc), (Status::Budget, 2));
+
+    let mut vm = VM::new(vec![1105, 1, 0]);
+    vm.set_max_instructions(Some(100));
+    let suspension = vm.resume(&[][..], Vec::new()).unwrap();
+    assert_eq!(
+        (suspension.status, suspension.count),
+        (Status::Watchdog(Limit::Instructions), 100)
+    );
+    vm.set_max_instructions(None);
+    vm.set_deadline(Some(Instant::now() + std::time::Duration::from_millis(10)));
+    let suspension = vm.resume(&[][..], Vec::new()).unwrap();
+    assert_eq!(suspension.status, Status::Watchdog(Limit::Deadline));
+
+    // The output at 0 rewrites itself into a halt; a stale decode would loop forever.
+    let cache = Arc::new(DecodeCache::new(&[104, 7, 1101, 0, 99, 0, 1105, 1, 0]));
+    let mut vm = VM::with_decode_cache(cache);
+    vm.set_max_instructions(Some(100));
+    let mut output = Vec::new();
+    let suspension = vm.resume(&[][..], &mut output).unwrap();
+    assert_eq!((suspension.status, output), (Status::Halt, vec![7]));
+
+    let mut vm = VM::new(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+    vm.add_watchpoint(3..4, Access::Write);
+    let suspension = vm.resume(&[][..], Vec::new()).unwrap();
+    let add = Instruction::from_slice(&[1, 9, 10, 3]).unwrap();
+    assert_eq!(
+        (suspension.status, suspension.pc),
+        (
+            Status::Watchpoint(Watch {
+                pc: 0,
+                instruction: add,
+                addr: 3,
+                access: Access::Write,
+                old: 3,
+                new: 70
+            }),
+            4
+        )
+    );
+    assert!(vm.remove_watchpoint(3..4));
+    vm.add_watchpoint(10..12, Access::ReadWrite);
+    let suspension = vm.resume(&[][..], Vec::new()).unwrap();
+    match suspension.status {
+        Status::Watchpoint(watch) => {
+            assert_eq!((watch.pc, watch.addr, watch.access), (4, 11, Access::Read));
+            assert_eq!((watch.old, watch.new), (50, 50));
+        }
+        status => panic!("unexpected {:?}", status),
+    }
+    let suspension = vm.resume(&[][..], Vec::new()).unwrap();
+    assert_eq!((suspension.status, vm.memory()[0]), (Status::Halt, 3500));
+
+    let mut vm = VM::new(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+    vm.set_history(Some(100));
+    vm.resume(&[][..], Vec::new()).unwrap();
+    vm.add_watchpoint(0..1, Access::Write);
+    let suspension = vm.reverse_continue();
+    match suspension.status {
+        Status::Watchpoint(watch) => {
+            assert_eq!((watch.pc, watch.addr), (4, 0));
+            assert_eq!((watch.old, watch.new), (1, 3500));
+        }
+        status => panic!("unexpected {:?}", status),
+    }
+    assert_eq!((vm.pc(), vm.count(), vm.memory()[0]), (4, 1, 1));
+    assert!(vm.step_back());
+    assert_eq!((vm.pc(), vm.memory()[3]), (0, 3));
+    assert_eq!(vm.reverse_continue().status, Status::StartOfHistory);
+
+    // Input consumed by an undone instruction is replayed; output is written again.
+    let mut vm = VM::new(vec![3, 5, 4, 5, 99, 0]);
+    vm.set_history(Some(100));
+    let mut output = Vec::new();
+    vm.resume(&[42][..], &mut output).unwrap();
+    assert!(vm.step_back() && vm.step_back() && !vm.step_back());
+    assert_eq!(
+        (vm.pc(), vm.memory()[5], vm.pending_inputs()[0]),
+        (0, 0, 42)
+    );
+    vm.set_history(Some(1));
+    vm.resume(&[][..], &mut output).unwrap();
+    assert_eq!((output, vm.history_len()), (vec![42, 42], 1));
+    let mut vm = VM::new(vec![3, 5, 4, 5, 99, 0]);
+    vm.set_history(Some(0));
+    vm.resume(&[42][..], Vec::new()).unwrap();
+    assert!(vm.history_len() == 0 && !vm.step_back());
+}